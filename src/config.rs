@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::Deserialize;
 use toml;
 
@@ -5,13 +7,38 @@ use toml;
 pub struct Config {
     pub image: ImageConfig,
     pub scene: SceneConfig,
-    pub camera: CameraConfig,
+    /// the first camera of the imported glTF scenes is used if it's missing
+    pub camera: Option<CameraConfig>,
 }
 
 impl Config {
     pub fn parse(toml_str: String) -> Result<Config, toml::de::Error> {
         toml::from_str(&toml_str)
     }
+
+    /// Configuration rendering the whole glTF scene with its camera, the image is named
+    /// after the file
+    pub fn new_gltf(path: &str) -> Config {
+        let name = std::path::Path::new(path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("gltf");
+        Config {
+            image: ImageConfig {
+                name: name.to_string(),
+                width: 800,
+                height: 600,
+            },
+            scene: SceneConfig {
+                gltf: vec![GltfConfig {
+                    path: path.to_string(),
+                    transform: None,
+                }],
+                ..SceneConfig::default()
+            },
+            camera: None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -19,6 +46,8 @@ pub struct CameraConfig {
     pub origin: [f32; 3],
     pub view: [f32; 3],
     pub up: [f32; 3],
+    /// field of view in degrees across the bigger side of the image (53.13 by default)
+    pub fov: Option<f32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,17 +57,498 @@ pub struct ImageConfig {
     pub height: usize,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 pub struct SceneConfig {
+    #[serde(default)]
     pub spheres: Vec<SphereConfig>,
+    #[serde(default)]
+    pub planes: Vec<PlaneConfig>,
+    #[serde(default)]
+    pub boxes: Vec<BoxConfig>,
+    #[serde(default)]
+    pub oriented_boxes: Vec<OrientedBoxConfig>,
+    #[serde(default)]
+    pub cylinders: Vec<CylinderConfig>,
+    #[serde(default)]
+    pub cones: Vec<ConeConfig>,
+    #[serde(default)]
+    pub tori: Vec<TorusConfig>,
+    #[serde(default)]
+    pub disks: Vec<DiskConfig>,
+    #[serde(default)]
+    pub quads: Vec<QuadConfig>,
+    #[serde(default)]
+    pub triangles: Vec<TriangleConfig>,
+    #[serde(default)]
+    pub meshes: Vec<MeshConfig>,
+    #[serde(default)]
+    pub csg: Vec<CsgConfig>,
+    #[serde(default)]
+    pub sdfs: Vec<SdfConfig>,
+    #[serde(default)]
+    pub heightfields: Vec<HeightfieldConfig>,
+    #[serde(default)]
+    pub metaballs: Vec<MetaballsConfig>,
+    #[serde(default)]
+    pub bezier_patches: Vec<BezierConfig>,
+    #[serde(default)]
+    pub quadrics: Vec<QuadricConfig>,
+    #[serde(default)]
+    pub curves: Vec<CurvesConfig>,
+    /// Named shapes which aren't rendered themselves, but are shared by instances
+    #[serde(default)]
+    pub geometries: HashMap<String, ShapeConfig>,
+    #[serde(default)]
+    pub instances: Vec<InstanceConfig>,
+    /// Scenes imported from glTF files with their own materials
+    #[serde(default)]
+    pub gltf: Vec<GltfConfig>,
+    /// the light is put at the camera if the scene has none
+    #[serde(default)]
     pub lights: Vec<LightConfig>,
+    /// how objects hit by rays are searched (bvh by default)
+    #[serde(default)]
+    pub accelerator: Accelerator,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Accelerator {
+    /// every object is tested by every ray
+    BruteForce,
+    /// bounding volume hierarchy over the objects built by the surface area heuristic
+    #[default]
+    Bvh,
+    /// uniform grid of cells, good for many small objects spread evenly
+    Grid,
+    /// space split by planes chosen by the surface area heuristic
+    KdTree,
+}
+
+impl Accelerator {
+    /// Parse the name used in the configuration
+    pub fn parse(name: &str) -> Option<Accelerator> {
+        match name {
+            "brute_force" => Some(Accelerator::BruteForce),
+            "bvh" => Some(Accelerator::Bvh),
+            "grid" => Some(Accelerator::Grid),
+            "kd_tree" => Some(Accelerator::KdTree),
+            _ => None,
+        }
+    }
+}
+
+/// Object of the scene: geometry of its shape and properties of its surface
+#[derive(Debug, Deserialize)]
+pub struct ObjectConfig<G> {
+    #[serde(flatten)]
+    pub geometry: G,
+    pub transform: Option<TransformConfig>,
+    pub properties: ObjProperties,
+}
+
+/// Scene imported from glTF 2.0 file: meshes with metallic-roughness materials, point
+/// lights and the camera
+#[derive(Debug, Deserialize)]
+pub struct GltfConfig {
+    /// Path to .gltf or .glb file
+    pub path: String,
+    /// transform of the whole imported scene
+    pub transform: Option<TransformConfig>,
+}
+
+/// Affine transformation of the shape: scale, then rotation (Euler angles, then axis-angle),
+/// then translation
+/// ```toml
+/// transform = { scale = [2.0, 1.0, 1.0], rotation = [0.0, 0.0, 45.0], translation = [0.0, 1.0, 0.0] }
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct TransformConfig {
+    /// scale along the coordinate axes
+    pub scale: Option<[f32; 3]>,
+    /// Euler angles in degrees around X, Y and Z axes applied in this order
+    pub rotation: Option<[f32; 3]>,
+    pub axis_angle: Option<AxisAngleConfig>,
+    pub translation: Option<[f32; 3]>,
 }
 
+/// Rotation around the axis by the angle in degrees
+#[derive(Debug, Deserialize)]
+pub struct AxisAngleConfig {
+    pub axis: [f32; 3],
+    pub angle: f32,
+}
+
+pub type SphereConfig = ObjectConfig<SphereGeometry>;
+pub type PlaneConfig = ObjectConfig<PlaneGeometry>;
+pub type BoxConfig = ObjectConfig<BoxGeometry>;
+pub type OrientedBoxConfig = ObjectConfig<OrientedBoxGeometry>;
+pub type CylinderConfig = ObjectConfig<CylinderGeometry>;
+pub type ConeConfig = ObjectConfig<ConeGeometry>;
+pub type TorusConfig = ObjectConfig<TorusGeometry>;
+pub type DiskConfig = ObjectConfig<DiskGeometry>;
+pub type QuadConfig = ObjectConfig<QuadGeometry>;
+pub type TriangleConfig = ObjectConfig<TriangleGeometry>;
+pub type MeshConfig = ObjectConfig<MeshGeometry>;
+pub type CsgConfig = ObjectConfig<CsgGeometry>;
+pub type SdfConfig = ObjectConfig<SdfGeometry>;
+pub type HeightfieldConfig = ObjectConfig<HeightfieldGeometry>;
+pub type MetaballsConfig = ObjectConfig<MetaballsGeometry>;
+pub type BezierConfig = ObjectConfig<BezierGeometry>;
+pub type QuadricConfig = ObjectConfig<QuadricGeometry>;
+pub type CurvesConfig = ObjectConfig<CurvesGeometry>;
+pub type InstanceConfig = ObjectConfig<InstanceGeometry>;
+
+/// Geometry of any shape, it's used where shapes are nested in each other:
+/// ```toml
+/// left = { sphere = { center = [0.0, 0.0, 0.0], radius = 1.0 } }
+/// ```
 #[derive(Debug, Deserialize)]
-pub struct SphereConfig {
+#[serde(rename_all = "snake_case")]
+pub enum ShapeConfig {
+    Sphere(SphereGeometry),
+    Plane(PlaneGeometry),
+    #[serde(rename = "box")]
+    AaBox(BoxGeometry),
+    OrientedBox(OrientedBoxGeometry),
+    Cylinder(CylinderGeometry),
+    Cone(ConeGeometry),
+    Torus(TorusGeometry),
+    Disk(DiskGeometry),
+    Quad(QuadGeometry),
+    Triangle(TriangleGeometry),
+    Mesh(MeshGeometry),
+    Csg(CsgGeometry),
+    Sdf(SdfGeometry),
+    Heightfield(HeightfieldGeometry),
+    Metaballs(MetaballsGeometry),
+    BezierPatches(BezierGeometry),
+    Quadric(QuadricGeometry),
+    Curves(CurvesGeometry),
+    Transformed(TransformedGeometry),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SphereGeometry {
     pub center: [f32; 3],
     pub radius: f32,
-    pub properties: ObjProperties,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaneGeometry {
+    pub point: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BoxGeometry {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrientedBoxGeometry {
+    pub center: [f32; 3],
+    pub half_extents: [f32; 3],
+    /// Euler angles in degrees around X, Y and Z axes applied in this order
+    #[serde(default)]
+    pub rotation: [f32; 3],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CylinderGeometry {
+    /// centers of the end disks
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+    pub radius: f32,
+    /// close the ends by disks (true by default)
+    pub caps: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConeGeometry {
+    /// centers of the end disks
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+    pub start_radius: f32,
+    pub end_radius: f32,
+    /// close the ends by disks (true by default)
+    pub caps: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TorusGeometry {
+    pub center: [f32; 3],
+    /// axis of the rotation symmetry of the torus
+    pub axis: [f32; 3],
+    /// radius of the circle going through the center of the tube
+    pub major_radius: f32,
+    /// radius of the tube
+    pub minor_radius: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiskGeometry {
+    pub center: [f32; 3],
+    pub normal: [f32; 3],
+    pub radius: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuadGeometry {
+    pub corner: [f32; 3],
+    /// edges of the parallelogram going from the corner
+    pub edge_u: [f32; 3],
+    pub edge_v: [f32; 3],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TriangleGeometry {
+    pub vertices: [[f32; 3]; 3],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MeshGeometry {
+    /// Path to the mesh file (.obj, .ply or .stl)
+    pub path: String,
+    /// Uniform scale of the mesh relative to the origin of coordinates
+    pub scale: Option<f32>,
+    /// Replace normals from the file by normals averaged over the faces around vertices
+    #[serde(default)]
+    pub smooth_normals: bool,
+    /// Faces meeting at the bigger angle in degrees keep the sharp edge between them when
+    /// normals are smoothed (no limit by default)
+    pub crease_angle: Option<f32>,
+    /// Refine the mesh by the subdivision surface scheme
+    pub subdivision: Option<SubdivisionConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubdivisionConfig {
+    pub scheme: SubdivisionScheme,
+    /// number of the subdivision steps, every one makes 4 times more faces
+    pub levels: usize,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SubdivisionScheme {
+    Loop,
+    CatmullClark,
+}
+
+/// Constructive solid geometry: solid made of two shapes by the operation
+#[derive(Debug, Deserialize)]
+pub struct CsgGeometry {
+    pub operation: CsgOperation,
+    pub left: Box<ShapeConfig>,
+    pub right: Box<ShapeConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// left shape without right one
+    Difference,
+}
+
+/// Terrain over the rectangle in the XZ plane with heights from the grayscale image
+#[derive(Debug, Deserialize)]
+pub struct HeightfieldGeometry {
+    /// Path to the image file (.pgm or .ppm), its columns go along X and rows along Z
+    pub path: String,
+    /// corner of the rectangle with min X and Z at zero height
+    pub origin: [f32; 3],
+    /// size of the rectangle along X and Z
+    pub size: [f32; 2],
+    /// height of the white pixel
+    pub height: f32,
+}
+
+/// Blobby surface where the sum of the fields of the balls is equal to the threshold
+#[derive(Debug, Deserialize)]
+pub struct MetaballsGeometry {
+    pub balls: Vec<BallConfig>,
+    pub threshold: f32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BallConfig {
+    pub center: [f32; 3],
+    /// radius of the influence of the ball, the field is zero farther
+    pub radius: f32,
+    /// strength of the field at the center, negative one carves other balls (1.0 by default)
+    pub weight: Option<f32>,
+}
+
+/// Surface made of bicubic Bézier patches, it's tessellated into triangles
+#[derive(Debug, Deserialize)]
+pub struct BezierGeometry {
+    /// Path to the file of patches in the format of the Utah teapot data
+    pub path: Option<String>,
+    /// Patches given by 16 control points row by row
+    #[serde(default)]
+    pub patches: Vec<[[f32; 3]; 16]>,
+    /// Max distance between the triangles and the surface (0.01 by default)
+    pub tolerance: Option<f32>,
+}
+
+/// Surface of the second degree, it's solid unless it's clipped by the bounds
+#[derive(Debug, Deserialize)]
+pub struct QuadricGeometry {
+    #[serde(flatten)]
+    pub surface: QuadricSurface,
+    /// Keep only the part of the surface inside the box, the surface becomes open
+    pub bounds: Option<BoundsConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuadricSurface {
+    /// a x^2 + b y^2 + c z^2 + d xy + e yz + f xz + g x + h y + i z + j = 0
+    Coefficients([f32; 10]),
+    /// semi-axes go along the coordinate axes
+    Ellipsoid { center: [f32; 3], radii: [f32; 3] },
+    /// paraboloid of revolution opening along the axis
+    Paraboloid {
+        vertex: [f32; 3],
+        axis: [f32; 3],
+        focal_length: f32,
+    },
+    /// hyperboloid of revolution around the axis
+    Hyperboloid {
+        center: [f32; 3],
+        axis: [f32; 3],
+        /// radius of the waist (distance from the center to the sheets for two sheets)
+        radius: f32,
+        /// semi-axis along the axis
+        half_length: f32,
+        /// hyperboloid of two sheets (false by default)
+        #[serde(default)]
+        two_sheets: bool,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BoundsConfig {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+/// Set of cubic Bézier curves (hair, grass, cables)
+#[derive(Debug, Deserialize)]
+pub struct CurvesGeometry {
+    /// Path to the text file of curves
+    pub path: Option<String>,
+    #[serde(default)]
+    pub curves: Vec<CurveConfig>,
+    /// tube by default
+    #[serde(default)]
+    pub style: CurveStyle,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CurveConfig {
+    pub points: [[f32; 3]; 4],
+    /// width at the start and at the end of the curve
+    pub width: [f32; 2],
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveStyle {
+    /// flat strip turned to the ray
+    Ribbon,
+    /// round tube
+    #[default]
+    Tube,
+}
+
+/// Reference to the shape from the geometries of the scene
+#[derive(Debug, Deserialize)]
+pub struct InstanceGeometry {
+    #[serde(rename = "geometry")]
+    pub name: String,
+}
+
+/// Nested shape moved by the transformation
+#[derive(Debug, Deserialize)]
+pub struct TransformedGeometry {
+    pub shape: Box<ShapeConfig>,
+    pub transform: TransformConfig,
+}
+
+/// Shape given by the signed distance field and rendered by sphere tracing
+#[derive(Debug, Deserialize)]
+pub struct SdfGeometry {
+    pub field: FieldConfig,
+    /// max number of steps along the ray (128 by default)
+    pub max_steps: Option<usize>,
+    /// distance to the surface at which the ray hits it (0.0001 by default)
+    pub epsilon: Option<f32>,
+    /// distance along the ray after which it misses the shape (1000 by default)
+    pub max_distance: Option<f32>,
+}
+
+/// Signed distance field made of primitives and their combinations:
+/// ```toml
+/// field = { smooth_union = { k = 0.5, left = { sphere = { center = [0.0, 0.0, 0.0], radius = 1.0 } }, right = ... } }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldConfig {
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+    },
+    /// box with edges rounded by the radius
+    RoundBox {
+        center: [f32; 3],
+        half_extents: [f32; 3],
+        radius: f32,
+    },
+    /// torus lying in the XZ plane
+    Torus {
+        center: [f32; 3],
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    /// cylinder with hemispheres at the ends
+    Capsule {
+        start: [f32; 3],
+        end: [f32; 3],
+        radius: f32,
+    },
+    Mandelbulb {
+        center: [f32; 3],
+        /// the fractal of scale 1.0 fits in the sphere of radius 1.2
+        scale: f32,
+        /// 8 by default
+        power: Option<f32>,
+        /// 8 by default
+        iterations: Option<usize>,
+    },
+    Union {
+        left: Box<FieldConfig>,
+        right: Box<FieldConfig>,
+    },
+    Intersection {
+        left: Box<FieldConfig>,
+        right: Box<FieldConfig>,
+    },
+    /// left field without right one
+    Difference {
+        left: Box<FieldConfig>,
+        right: Box<FieldConfig>,
+    },
+    /// union blending the surfaces where they are closer than k
+    SmoothUnion {
+        left: Box<FieldConfig>,
+        right: Box<FieldConfig>,
+        k: f32,
+    },
 }
 
 #[derive(Debug, Deserialize)]
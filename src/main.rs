@@ -4,43 +4,98 @@ pub mod raytracer;
 
 use std::process::ExitCode;
 
+const USAGE: &str = "usage: raytracer [--accelerator brute_force|bvh|grid|kd_tree] \
+                     [--threads <number>] <path_to_toml_config | path_to_gltf_scene>";
+
 fn main() -> ExitCode {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2 {
-        println!("usage: raytracer <path_to_toml_config>");
-        return ExitCode::FAILURE;
+    let mut config_path = None;
+    // overrides the accelerator of the configuration
+    let mut accelerator = None;
+    // all the cores by default
+    let mut threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--accelerator" => match args.next().as_deref().and_then(config::Accelerator::parse) {
+                Some(a) => accelerator = Some(a),
+                None => {
+                    println!("{}", USAGE);
+                    return ExitCode::FAILURE;
+                }
+            },
+            "--threads" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) if n > 0 => threads = n,
+                _ => {
+                    println!("{}", USAGE);
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ => config_path = Some(arg),
+        }
     }
-
-    let config_path = &args[1];
-    let config_str = match std::fs::read_to_string(config_path) {
-        Ok(str) => str,
-        Err(err) => {
-            println!("fail to read file {}: {}", config_path, err.to_string());
+    let config_path = match &config_path {
+        Some(path) => path,
+        None => {
+            println!("{}", USAGE);
             return ExitCode::FAILURE;
         }
     };
 
-    let cfg = match config::Config::parse(config_str) {
-        Ok(cfg) => cfg,
-        Err(err) => {
-            println!("fail to parse file {}: {}", config_path, err.message());
-            return ExitCode::FAILURE;
+    let is_gltf = [".gltf", ".glb"]
+        .iter()
+        .any(|ext| config_path.to_lowercase().ends_with(ext));
+    let mut cfg = if is_gltf {
+        config::Config::new_gltf(config_path)
+    } else {
+        match read_config(config_path) {
+            Some(cfg) => cfg,
+            None => return ExitCode::FAILURE,
         }
     };
 
+    if let Some(a) = accelerator {
+        cfg.scene.accelerator = a;
+    }
+
     let mut image = image::RasterImage::new(cfg.image);
 
-    raytracer::Raytracer::new(cfg.camera, cfg.scene).fill_image(&mut image);
+    let raytracer = match raytracer::Raytracer::new(cfg.camera, cfg.scene, image.get_resolution()) {
+        Ok(raytracer) => raytracer,
+        Err(err) => {
+            println!("fail to load scene: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    raytracer.fill_image(&mut image, threads);
 
     match image.save_ppm() {
         Ok(_) => {
             println!("ppm image successful save");
         }
         Err(err) => {
-            println!("fail to read file {}: {}", config_path, err.to_string());
+            println!("fail to read file {}: {}", config_path, err);
             return ExitCode::FAILURE;
         }
     }
 
     ExitCode::SUCCESS
 }
+
+/// Read and parse TOML configuration, errors are printed
+fn read_config(config_path: &str) -> Option<config::Config> {
+    let config_str = match std::fs::read_to_string(config_path) {
+        Ok(str) => str,
+        Err(err) => {
+            println!("fail to read file {}: {}", config_path, err);
+            return None;
+        }
+    };
+
+    match config::Config::parse(config_str) {
+        Ok(cfg) => Some(cfg),
+        Err(err) => {
+            println!("fail to parse file {}: {}", config_path, err.message());
+            None
+        }
+    }
+}
@@ -1,10 +1,13 @@
 mod canvas;
+mod matrix;
+mod packet;
 mod ray;
 mod scene;
 mod vector;
 
 use canvas::Camera;
 use canvas::Canvas;
+use scene::LoadError;
 use scene::Scene;
 
 use crate::config::CameraConfig;
@@ -17,14 +20,34 @@ pub struct Raytracer {
 }
 
 impl Raytracer {
-    pub fn new(camera: CameraConfig, scene: SceneConfig) -> Raytracer {
-        Raytracer {
+    /// Camera of the imported glTF scene is used if the camera isn't set, its field of view
+    /// is fitted to the resolution (width, height) of the image
+    pub fn new(
+        camera: Option<CameraConfig>,
+        scene: SceneConfig,
+        resolution: (usize, usize),
+    ) -> Result<Raytracer, LoadError> {
+        let mut scene = Scene::new(scene)?;
+        let (width, height) = resolution;
+        let aspect = width as f32 / height as f32;
+        let camera = camera
+            .or_else(|| scene.take_camera(aspect))
+            .ok_or_else(|| {
+                LoadError::new(
+                    "camera",
+                    None,
+                    "camera isn't set and imported scenes have none".to_string(),
+                )
+            })?;
+        scene.light_from_camera(&camera);
+        Ok(Raytracer {
             camera: Camera::new(camera),
-            scene: Scene::new(scene),
-        }
+            scene,
+        })
     }
 
-    pub fn fill_image(self, image: &mut RasterImage) {
+    /// Render the image by the number of threads
+    pub fn fill_image(self, image: &mut RasterImage, threads: usize) {
         let (width, height) = image.get_resolution();
         let resolution = std::cmp::max(width, height);
         let mut convas = Canvas::new(self.camera, self.scene, resolution + 1);
@@ -35,10 +58,10 @@ impl Raytracer {
             (0, (resolution - width) / 2)
         };
 
-        // TODO make parallel
         convas.fill_canvas(
             (w_shift, resolution - w_shift + 1),
             (h_shift, resolution - h_shift + 1),
+            threads,
         );
 
         for j in 0..height {
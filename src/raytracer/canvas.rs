@@ -1,5 +1,13 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
 use crate::config::CameraConfig;
 use crate::image::Color;
+use crate::raytracer::packet::{RayPacket, LANES};
 use crate::raytracer::ray::Ray;
 use crate::raytracer::scene::Scene;
 use crate::raytracer::vector::Vector;
@@ -9,6 +17,8 @@ pub struct Camera {
     view: Vector,
     tau: Vector,
     up: Vector,
+    /// size of the view at the distance 1 from the origin
+    size: f32,
 }
 
 impl Camera {
@@ -22,10 +32,16 @@ impl Camera {
             view,
             tau,
             up,
+            size: conf
+                .fov
+                .map_or(1.0, |fov| 2.0 * (0.5 * fov.to_radians()).tan()),
         }
     }
 }
 
+/// Side of the square tile of the canvas rendered by one thread at once
+const TILE_SIZE: usize = 32;
+
 pub struct Canvas {
     camera: Camera,
     scene: Scene,
@@ -50,18 +66,85 @@ impl Canvas {
         self.canvas.get(index)
     }
 
-    pub fn fill_canvas(&mut self, i_bound: (usize, usize), j_bound: (usize, usize)) {
-        for j in j_bound.0..j_bound.1 {
-            for i in i_bound.0..i_bound.1 {
-                let ray = self.get_ray(i, j);
-                let ray_color = self.scene.get_ray_color(&ray, 0);
+    /// Render the pixels in the bounds by the threads, the threads take tiles of the canvas
+    /// one by one until all of them are rendered
+    pub fn fill_canvas(
+        &mut self,
+        i_bound: (usize, usize),
+        j_bound: (usize, usize),
+        threads: usize,
+    ) {
+        let mut tiles = Vec::new();
+        for j in (j_bound.0..j_bound.1).step_by(TILE_SIZE) {
+            for i in (i_bound.0..i_bound.1).step_by(TILE_SIZE) {
+                tiles.push((
+                    i..(i + TILE_SIZE).min(i_bound.1),
+                    j..(j + TILE_SIZE).min(j_bound.1),
+                ));
+            }
+        }
+
+        let canvas = &*self;
+        let next_tile = AtomicUsize::new(0);
+        let rendered: Vec<(usize, Vec<Color>)> = thread::scope(|s| {
+            let workers: Vec<_> = (0..threads.max(1))
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut rendered = Vec::new();
+                        loop {
+                            let t = next_tile.fetch_add(1, Ordering::Relaxed);
+                            let (i_range, j_range) = match tiles.get(t) {
+                                Some(tile) => tile,
+                                None => return rendered,
+                            };
+                            let colors = canvas.render_tile(i_range, j_range, t);
+                            rendered.push((t, colors));
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().expect("render thread panicked"))
+                .collect()
+        });
+
+        for (t, colors) in rendered {
+            let (i_range, j_range) = &tiles[t];
+            let pixels = j_range
+                .clone()
+                .flat_map(|j| i_range.clone().map(move |i| (i, j)));
+            for ((i, j), color) in pixels.zip(colors) {
                 let pixel = match self.get_canvas_pixel_mut(i, j) {
                     Some(pixel) => pixel,
                     None => continue,
                 };
-                pixel.set(&ray_color);
+                pixel.set(&color);
+            }
+        }
+    }
+
+    /// Return colors of the tile pixels row by row, the random numbers of the tile are seeded
+    /// by its number, so the image doesn't depend on the number of threads. Neighbour pixels
+    /// of the row are traced together in packets.
+    fn render_tile(
+        &self,
+        i_range: &Range<usize>,
+        j_range: &Range<usize>,
+        tile: usize,
+    ) -> Vec<Color> {
+        let mut rng = StdRng::seed_from_u64(tile as u64);
+        let mut colors = Vec::with_capacity(i_range.len() * j_range.len());
+        for j in j_range.clone() {
+            for i in i_range.clone().step_by(LANES) {
+                let rays = (i..(i + LANES).min(i_range.end))
+                    .map(|i| self.get_ray(i, j))
+                    .collect();
+                let packet = RayPacket::new(rays);
+                colors.extend(self.scene.get_packet_colors(&packet, &mut rng));
             }
         }
+        colors
     }
 
     fn get_canvas_pixel_mut(&mut self, i: usize, j: usize) -> Option<&mut Color> {
@@ -71,8 +154,8 @@ impl Canvas {
 
     fn get_ray(&self, i: usize, j: usize) -> Ray {
         let mut dir = self.camera.view.clone();
-        let h_shift = -0.5 + (i as f32 * self.step + self.step * 0.5);
-        let v_shift = 0.5 - (j as f32 * self.step + self.step * 0.5);
+        let h_shift = (-0.5 + (i as f32 * self.step + self.step * 0.5)) * self.camera.size;
+        let v_shift = (0.5 - (j as f32 * self.step + self.step * 0.5)) * self.camera.size;
 
         dir += &(&self.camera.tau * h_shift);
         dir += &(&self.camera.up * v_shift);
@@ -0,0 +1,218 @@
+use std::ops::Mul;
+
+use crate::raytracer::vector::Vector;
+
+/// Matrix 4x4 of affine transformation of the Euclidean space.
+///
+/// Points and Vectors are columns, so transformation applied first goes last in the product:
+/// ```rust
+/// // scale, then rotate, then translate
+/// let m = &(&translation * &rotation) * &scale;
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+    m: [[f32; 4]; 4],
+}
+
+impl Matrix {
+    pub fn identity() -> Matrix {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Matrix { m }
+    }
+
+    pub fn translation(v: &Vector) -> Matrix {
+        let mut res = Matrix::identity();
+        for (i, c) in v.to_arr().into_iter().enumerate() {
+            res.m[i][3] = c;
+        }
+        res
+    }
+
+    /// Scale along the coordinate axes
+    pub fn scale(k: &Vector) -> Matrix {
+        let mut res = Matrix::identity();
+        for (i, c) in k.to_arr().into_iter().enumerate() {
+            res.m[i][i] = c;
+        }
+        res
+    }
+
+    /// Rotation by Euler angles in degrees around X, Y and Z axes applied in this order
+    pub fn rotation(angles: &[f32; 3]) -> Matrix {
+        let axes = [
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+        ];
+        let [x, y, z] = [0, 1, 2].map(|i| Matrix::rotation_axis(&axes[i], angles[i]));
+        &(&z * &y) * &x
+    }
+
+    /// Rotation around the axis by the angle in degrees (counterclockwise looking from the
+    /// end of the axis)
+    pub fn rotation_axis(axis: &Vector, angle: f32) -> Matrix {
+        let [x, y, z] = axis.clone().norm().to_arr();
+        let (s, c) = angle.to_radians().sin_cos();
+        let t = 1.0 - c;
+        Matrix {
+            m: [
+                [t * x * x + c, t * x * y - s * z, t * x * z + s * y, 0.0],
+                [t * x * y + s * z, t * y * y + c, t * y * z - s * x, 0.0],
+                [t * x * z - s * y, t * y * z + s * x, t * z * z + c, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Rotation by the unit quaternion [x, y, z, w]
+    pub fn rotation_quaternion(q: &[f32; 4]) -> Matrix {
+        let [x, y, z, w] = *q;
+        Matrix {
+            m: [
+                [
+                    1.0 - 2.0 * (y * y + z * z),
+                    2.0 * (x * y - z * w),
+                    2.0 * (x * z + y * w),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * y + z * w),
+                    1.0 - 2.0 * (x * x + z * z),
+                    2.0 * (y * z - x * w),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * z - y * w),
+                    2.0 * (y * z + x * w),
+                    1.0 - 2.0 * (x * x + y * y),
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Matrix from its elements stored column by column
+    pub fn from_columns(c: &[f32; 16]) -> Matrix {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, e) in row.iter_mut().enumerate() {
+                *e = c[4 * j + i];
+            }
+        }
+        Matrix { m }
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, c) in row.iter_mut().enumerate() {
+                *c = self.m[j][i];
+            }
+        }
+        Matrix { m }
+    }
+
+    /// Return inverse matrix by Gauss-Jordan elimination, None if the matrix is singular
+    /// (for example scale by zero)
+    pub fn inverse(&self) -> Option<Matrix> {
+        let mut a = self.m.map(|row| row.map(|c| c as f64));
+        let mut inv = Matrix::identity().m.map(|row| row.map(|c| c as f64));
+
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let k = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= k;
+                inv[col][j] *= k;
+            }
+            for i in (0..4).filter(|&i| i != col) {
+                let f = a[i][col];
+                for j in 0..4 {
+                    a[i][j] -= f * a[col][j];
+                    inv[i][j] -= f * inv[col][j];
+                }
+            }
+        }
+        Some(Matrix {
+            m: inv.map(|row| row.map(|c| c as f32)),
+        })
+    }
+
+    /// Transform the point, it's moved by the translation
+    pub fn point(&self, p: &Vector) -> Vector {
+        let p = p.to_arr();
+        let res = [0, 1, 2].map(|i| {
+            let r = &self.m[i];
+            r[0] * p[0] + r[1] * p[1] + r[2] * p[2] + r[3]
+        });
+        Vector::new_from_arr(&res)
+    }
+
+    /// Transform the Vector (direction), it isn't moved by the translation
+    pub fn vector(&self, v: &Vector) -> Vector {
+        let v = v.to_arr();
+        let res = [0, 1, 2].map(|i| {
+            let r = &self.m[i];
+            r[0] * v[0] + r[1] * v[1] + r[2] * v[2]
+        });
+        Vector::new_from_arr(&res)
+    }
+}
+
+impl Mul for &Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, c) in row.iter_mut().enumerate() {
+                *c = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Matrix { m }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::matrix::Matrix;
+    use crate::raytracer::vector::Vector;
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let m = &(&Matrix::translation(&Vector::new(1.0, -2.0, 3.0))
+            * &Matrix::rotation(&[30.0, 45.0, 60.0]))
+            * &Matrix::scale(&Vector::new(2.0, 0.5, 1.0));
+        let p = Vector::new(0.3, 0.7, -1.1);
+
+        let back = m.inverse().unwrap().point(&m.point(&p));
+        let diff = &back - &p;
+        assert!(diff.size() < 1e-5, "{:?} != {:?}", back, p);
+    }
+
+    #[test]
+    fn rotation_around_z() {
+        let r = Matrix::rotation(&[0.0, 0.0, 90.0]);
+        let v = r.vector(&Vector::new(1.0, 0.0, 0.0));
+        assert!((&v - &Vector::new(0.0, 1.0, 0.0)).size() < 1e-6);
+    }
+
+    #[test]
+    fn quaternion_is_rotation_around_axis() {
+        let (s, c) = 30.0_f32.to_radians().sin_cos();
+        let q = Matrix::rotation_quaternion(&[0.6 * s, 0.0, 0.8 * s, c]);
+        let r = Matrix::rotation_axis(&Vector::new(0.6, 0.0, 0.8), 60.0);
+        let v = Vector::new(0.3, -1.2, 2.0);
+        assert!((&q.vector(&v) - &r.vector(&v)).size() < 1e-5);
+    }
+}
@@ -0,0 +1,77 @@
+//! Packets of coherent rays traced together.
+//!
+//! Coordinates of the rays are stored by lanes, so the same operation on all the rays of the
+//! packet is a loop over the lanes which the compiler turns into SIMD instructions.
+
+use crate::raytracer::ray::Ray;
+
+/// Number of the rays in the packet, 4 floats fill the SIMD register of any x86_64 or
+/// aarch64 processor
+pub const LANES: usize = 4;
+
+/// Values of the rays of the packet
+pub type Lanes = [f32; LANES];
+
+/// Return the lanes made by the function of the lane number
+pub fn lanes<F: FnMut(usize) -> f32>(f: F) -> Lanes {
+    std::array::from_fn(f)
+}
+
+pub struct RayPacket {
+    rays: Vec<Ray>,
+    /// coordinates X, Y, Z of the origins
+    orig: [Lanes; 3],
+    dir: [Lanes; 3],
+    /// 1 / direction
+    inv_dir: [Lanes; 3],
+}
+
+impl RayPacket {
+    /// Make the packet of 1 to LANES rays, free lanes repeat the first ray and their results
+    /// are ignored
+    pub fn new(rays: Vec<Ray>) -> RayPacket {
+        assert!(
+            !rays.is_empty() && rays.len() <= LANES,
+            "packet of {} rays",
+            rays.len()
+        );
+        let coords = |f: fn(&Ray) -> [f32; 3]| {
+            let arrs: Vec<[f32; 3]> = rays.iter().map(f).collect();
+            [0, 1, 2].map(|axis| lanes(|l| arrs.get(l).unwrap_or(&arrs[0])[axis]))
+        };
+        let orig = coords(|r| r.get_orig().to_arr());
+        let dir = coords(|r| r.get_dir().to_arr());
+        let inv_dir = dir.map(|d| d.map(|d| 1.0 / d));
+        RayPacket {
+            rays,
+            orig,
+            dir,
+            inv_dir,
+        }
+    }
+
+    /// Return the number of the rays, the rest of the lanes are free
+    pub fn count(&self) -> usize {
+        self.rays.len()
+    }
+
+    pub fn ray(&self, lane: usize) -> &Ray {
+        &self.rays[lane]
+    }
+
+    pub fn rays(&self) -> &[Ray] {
+        &self.rays
+    }
+
+    pub fn orig(&self) -> &[Lanes; 3] {
+        &self.orig
+    }
+
+    pub fn dir(&self) -> &[Lanes; 3] {
+        &self.dir
+    }
+
+    pub fn inv_dir(&self) -> &[Lanes; 3] {
+        &self.inv_dir
+    }
+}
@@ -1,13 +1,29 @@
-use crate::config::{ObjProperties, SceneConfig};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::config::{
+    Accelerator, CameraConfig, GltfConfig, InstanceConfig, ObjProperties, ObjectConfig, SceneConfig,
+};
 use crate::image::Color;
+use crate::raytracer::matrix::Matrix;
+use crate::raytracer::packet::RayPacket;
 use crate::raytracer::ray::Ray;
 use crate::raytracer::vector::Vector;
 
+mod accelerator;
+mod geometry;
 mod light;
+mod loader;
 mod shape;
 
+use accelerator::ObjectAccelerator;
+use geometry::Geometry;
 use light::Light;
-use shape::Shape;
+pub use loader::LoadError;
+use loader::{GltfCamera, Material};
+use shape::{Mesh, Shape};
 
 pub struct Object {
     shape: Box<dyn Shape>,
@@ -28,25 +44,67 @@ impl Properties {
             reflection: cfg.reflection,
         }
     }
+
+    /// Properties of the glTF material: metals reflect the more the smoother they are and
+    /// roughness blurs the reflection
+    fn new_material(m: &Material) -> Properties {
+        let reflection = m.metallic_factor * (1.0 - m.roughness_factor);
+        Properties {
+            color: m.color(),
+            diffuse: (reflection > 0.0 && m.roughness_factor > 0.0).then_some(m.roughness_factor),
+            reflection: (reflection > 0.0).then_some(reflection),
+        }
+    }
 }
 
 pub struct Scene {
     objects: Vec<Object>,
     lights: Vec<Light>,
+    /// camera of the first imported glTF scene which has one
+    camera: Option<GltfCamera>,
+    /// search of the objects hit by rays
+    accelerator: ObjectAccelerator,
 }
 
 impl Scene {
-    pub fn new(cfg: SceneConfig) -> Scene {
+    pub fn new(cfg: SceneConfig) -> Result<Scene, LoadError> {
         let mut scene = Scene {
             objects: Vec::new(),
             lights: Vec::new(),
+            camera: None,
+            accelerator: ObjectAccelerator::new(Accelerator::BruteForce, Vec::new()),
         };
         // objects
-        for s in cfg.spheres.iter() {
-            let sphere = shape::new_sphere(Vector::new_from_arr(&s.center), s.radius);
-            let prop = Properties::new(&s.properties);
+        scene.push_objects(&cfg.spheres)?;
+        scene.push_objects(&cfg.planes)?;
+        scene.push_objects(&cfg.boxes)?;
+        scene.push_objects(&cfg.oriented_boxes)?;
+        scene.push_objects(&cfg.cylinders)?;
+        scene.push_objects(&cfg.cones)?;
+        scene.push_objects(&cfg.tori)?;
+        scene.push_objects(&cfg.disks)?;
+        scene.push_objects(&cfg.quads)?;
+        scene.push_objects(&cfg.triangles)?;
+        scene.push_objects(&cfg.meshes)?;
+        scene.push_objects(&cfg.csg)?;
+        scene.push_objects(&cfg.sdfs)?;
+        scene.push_objects(&cfg.heightfields)?;
+        scene.push_objects(&cfg.metaballs)?;
+        scene.push_objects(&cfg.bezier_patches)?;
+        scene.push_objects(&cfg.quadrics)?;
+        scene.push_objects(&cfg.curves)?;
 
-            scene.push_object(sphere, prop)
+        // instances
+        let mut geometries = HashMap::new();
+        for (name, g) in cfg.geometries.iter() {
+            let shape: Arc<dyn Shape> = Arc::from(g.new_shape()?);
+            geometries.insert(name.clone(), shape);
+        }
+        scene.push_instances(&cfg.instances, &geometries)?;
+
+        // imported scenes
+        for g in cfg.gltf.iter() {
+            scene.push_gltf(g)?;
         }
 
         // light
@@ -55,14 +113,101 @@ impl Scene {
             scene.push_light(light);
         }
 
-        scene
+        let bounds = scene.objects.iter().map(|o| o.shape.bounds()).collect();
+        scene.accelerator = ObjectAccelerator::new(cfg.accelerator, bounds);
+
+        Ok(scene)
     }
 
-    fn push_object<T: Shape + 'static>(&mut self, shape: T, properties: Properties) {
-        let obj = Object {
-            properties,
-            shape: Box::new(shape),
-        };
+    fn push_objects<G: Geometry>(&mut self, objects: &[ObjectConfig<G>]) -> Result<(), LoadError> {
+        for o in objects.iter() {
+            let shape = o.geometry.new_shape()?;
+            self.push_configured_object(shape, o)?;
+        }
+        Ok(())
+    }
+
+    /// Push instances of the shared geometries, the shape is shared, but every instance has
+    /// its own transform and properties
+    fn push_instances(
+        &mut self,
+        instances: &[InstanceConfig],
+        geometries: &HashMap<String, Arc<dyn Shape>>,
+    ) -> Result<(), LoadError> {
+        for o in instances.iter() {
+            let shape = geometries.get(&o.geometry.name).ok_or_else(|| {
+                LoadError::new(
+                    &o.geometry.name,
+                    None,
+                    "geometry is not defined".to_string(),
+                )
+            })?;
+            self.push_configured_object(Box::new(Arc::clone(shape)), o)?;
+        }
+        Ok(())
+    }
+
+    /// Push meshes and lights of the glTF scene, its camera is kept if the scene has none yet
+    fn push_gltf(&mut self, cfg: &GltfConfig) -> Result<(), LoadError> {
+        let root = cfg
+            .transform
+            .as_ref()
+            .map_or_else(Matrix::identity, geometry::transform_matrix);
+        let gltf = loader::load_gltf(&cfg.path, &root)?;
+        let meshes: Vec<(Option<Arc<Mesh>>, Material)> = gltf
+            .meshes
+            .into_iter()
+            .map(|mesh| {
+                // points and lines have no faces
+                let shape = (!mesh.data.faces.is_empty()).then(|| Arc::new(Mesh::new(mesh.data)));
+                (shape, mesh.material)
+            })
+            .collect();
+        for (i, m) in gltf.instances.iter() {
+            let (Some(shape), material) = &meshes[*i] else {
+                continue;
+            };
+            // nodes scaled by zero have no surface
+            if let Some(t) = shape::new_transformed(Box::new(Arc::clone(shape)), m) {
+                self.push_object(Box::new(t), Properties::new_material(material));
+            }
+        }
+        for l in gltf.lights {
+            self.push_light(Light::new(l));
+        }
+        if self.camera.is_none() {
+            self.camera = gltf.camera;
+        }
+        Ok(())
+    }
+
+    /// Return the camera of the imported glTF scenes for the image with the aspect ratio
+    /// (width to height)
+    pub fn take_camera(&mut self, aspect: f32) -> Option<CameraConfig> {
+        self.camera.take().map(|c| camera_config(c, aspect))
+    }
+
+    /// Put the light at the camera if the scene has none, imported scenes often have no
+    /// lights of their own
+    pub fn light_from_camera(&mut self, camera: &CameraConfig) {
+        if self.lights.is_empty() {
+            self.push_light(Light::new(Vector::new_from_arr(&camera.origin)));
+        }
+    }
+
+    fn push_configured_object<G>(
+        &mut self,
+        mut shape: Box<dyn Shape>,
+        cfg: &ObjectConfig<G>,
+    ) -> Result<(), LoadError> {
+        if let Some(t) = &cfg.transform {
+            shape = geometry::transform(shape, t)?;
+        }
+        self.push_object(shape, Properties::new(&cfg.properties));
+        Ok(())
+    }
+    fn push_object(&mut self, shape: Box<dyn Shape>, properties: Properties) {
+        let obj = Object { properties, shape };
         self.objects.push(obj)
     }
     fn push_light(&mut self, light: Light) {
@@ -71,83 +216,140 @@ impl Scene {
 
     const REFLECT_DEEP: u8 = 5;
 
-    pub fn get_ray_color(&self, ray: &Ray, deep: u8) -> Color {
+    /// Random numbers for the diffusion are drawn from rng
+    pub fn get_ray_color<R: Rng>(&self, ray: &Ray, deep: u8, rng: &mut R) -> Color {
+        match self.intersec(ray) {
+            None => Color::new(0, 0, 0),
+            Some(intersec) => self.get_intersec_color(ray, &intersec, deep, rng),
+        }
+    }
+
+    /// Return colors of the rays of the packet, the rays are traced together to the first
+    /// hit, the rest of their paths is traced one by one
+    pub fn get_packet_colors<R: Rng>(&self, packet: &RayPacket, rng: &mut R) -> Vec<Color> {
+        let objs = self.intersec_obj_packet(packet);
+        packet
+            .rays()
+            .iter()
+            .zip(objs)
+            .map(|(ray, obj)| match obj {
+                None => Color::new(0, 0, 0),
+                Some(obj) => self.get_intersec_color(ray, &Intersection::new(obj, ray), 0, rng),
+            })
+            .collect()
+    }
+
+    fn get_intersec_color<R: Rng>(
+        &self,
+        ray: &Ray,
+        intersec: &Intersection,
+        deep: u8,
+        rng: &mut R,
+    ) -> Color {
         let mut c = Color::new(0, 0, 0);
         for l in self.lights.iter() {
-            let ray_color_by_l = self.get_ray_color_by_light(ray, &l, deep);
+            let ray_color_by_l = self.get_ray_color_by_light(ray, intersec, l, deep, rng);
             c = &c + &ray_color_by_l;
         }
         c
     }
 
-    fn get_rlf_ray(ray: &Ray, p: &Vector, n: &Vector, deep: u8) -> Option<Ray> {
+    /// Reflection is made by the shading normal n, but the ray starts a step away from the
+    /// real surface along its normal geom_n
+    fn get_rlf_ray(ray: &Ray, p: &Vector, geom_n: &Vector, n: &Vector, deep: u8) -> Option<Ray> {
         if deep >= Self::REFLECT_DEEP {
             return None;
         }
 
-        ray.new_reflect(&p.step_away(&n), n)
+        ray.new_reflect(&p.step_away(geom_n), n)
     }
 
     fn is_shadow(&self, l: &Light, p: &Vector, n: &Vector) -> bool {
         let pl = l.get_orig() - p;
         let pl_size = pl.size();
 
-        let sh_ray = Ray::new(p.step_away(&n), pl.norm());
+        let sh_ray = Ray::new(p.step_away(n), pl.norm());
 
-        let distance = match self.intersec_obj(&sh_ray) {
-            None => f32::MAX,
-            Some(intersec) => intersec.distance,
-        };
-
-        distance <= pl_size
+        self.accelerator.any_hit(&sh_ray, pl_size, |i| {
+            self.objects[i].shape.intersec(&sh_ray).get_closer()
+        })
     }
 
-    fn get_ray_color_by_light(&self, ray: &Ray, l: &Light, deep: u8) -> Color {
-        let intersec = match self.intersec(ray) {
-            None => return Color::new(0, 0, 0),
-            Some(intersec) => intersec,
-        };
+    fn get_ray_color_by_light<R: Rng>(
+        &self,
+        ray: &Ray,
+        intersec: &Intersection,
+        l: &Light,
+        deep: u8,
+        rng: &mut R,
+    ) -> Color {
         let norm = match intersec.obj_properties.diffuse {
-            None => intersec.norm,
-            Some(diff) => (&intersec.norm + &(&Vector::new_rand() * diff)).norm(),
+            None => intersec.shading_norm.clone(),
+            Some(diff) => (&intersec.shading_norm + &(&Vector::new_rand(rng) * diff)).norm(),
         };
-        let rfl_handler = |c: Color| match intersec.obj_properties.reflection {
+        let mut rfl_handler = |c: Color| match intersec.obj_properties.reflection {
             None => c,
             Some(rfl) => {
-                let rfl_ray = match Self::get_rlf_ray(ray, &intersec.point, &norm, deep) {
-                    None => return c,
-                    Some(r) => r,
-                };
-                c.add_refl(rfl, self.get_ray_color(&rfl_ray, deep + 1))
+                let rfl_ray =
+                    match Self::get_rlf_ray(ray, &intersec.point, &intersec.norm, &norm, deep) {
+                        None => return c,
+                        Some(r) => r,
+                    };
+                c.add_refl(rfl, self.get_ray_color(&rfl_ray, deep + 1, rng))
             }
         };
 
         // shadow
-        if self.is_shadow(l, &intersec.point, &norm) {
+        if self.is_shadow(l, &intersec.point, &intersec.norm) {
             return rfl_handler(Color::new(0, 0, 0));
         }
 
         let dist_from_l = (&intersec.point - l.get_orig()).size();
         let l_intens = l.intensity(&intersec.point, &norm) / (dist_from_l);
 
-        rfl_handler(l_intens * intersec.obj_properties.color.clone())
+        rfl_handler(l_intens * intersec.color.clone())
+    }
+}
+
+/// Convert the glTF camera, its vertical field of view becomes the one across the bigger side
+/// of the image with the aspect ratio
+fn camera_config(c: GltfCamera, aspect: f32) -> CameraConfig {
+    let fov = c.yfov.map(|yfov| {
+        if aspect > 1.0 {
+            2.0 * (aspect * (0.5 * yfov).tan()).atan().to_degrees()
+        } else {
+            yfov.to_degrees()
+        }
+    });
+    CameraConfig {
+        origin: c.origin.to_arr(),
+        view: c.view.to_arr(),
+        up: c.up.to_arr(),
+        fov,
     }
 }
 
 struct Intersection<'a> {
     point: Vector,
+    /// normal of the real surface
     norm: Vector,
+    /// normal used for shading, it's the same as norm if the shape has no other one
+    shading_norm: Vector,
+    color: Color,
     obj_properties: &'a Properties,
 }
 
 impl<'a> Intersection<'a> {
     fn new(obj: IntersecObj<'a>, ray: &Ray) -> Intersection<'a> {
-        let point = ray.point_on_ray(obj.distance);
-        let obj = obj.obj;
+        let hit = obj.obj.shape.hit(ray, obj.distance);
         Intersection {
-            norm: obj.shape.norm(&point),
-            obj_properties: &obj.properties,
-            point,
+            point: hit.point,
+            shading_norm: hit.shading_norm.unwrap_or_else(|| hit.norm.clone()),
+            norm: hit.norm,
+            color: hit
+                .color
+                .unwrap_or_else(|| obj.obj.properties.color.clone()),
+            obj_properties: &obj.obj.properties,
         }
     }
 }
@@ -158,32 +360,33 @@ struct IntersecObj<'a> {
 }
 
 impl Scene {
-    fn intersec_obj(&self, ray: &Ray) -> Option<IntersecObj> {
-        let mut nearest_obj = None;
-        for obj in self.objects.iter() {
-            let distance = match obj.shape.intersec(ray).get_closer() {
-                Some(d) => d,
-                None => continue,
-            };
+    fn intersec_obj(&self, ray: &Ray) -> Option<IntersecObj<'_>> {
+        let (i, distance) = self
+            .accelerator
+            .closest(ray, |i| self.objects[i].shape.intersec(ray).get_closer())?;
+        Some(IntersecObj {
+            obj: &self.objects[i],
+            distance,
+        })
+    }
 
-            nearest_obj = match nearest_obj {
-                None => Some(IntersecObj { obj, distance }),
-                Some(mut nearest_obj) => {
-                    if distance < nearest_obj.distance {
-                        nearest_obj.distance = distance;
-                        nearest_obj.obj = obj;
-                    }
-                    Some(nearest_obj)
-                }
-            };
-        }
-        nearest_obj
+    fn intersec_obj_packet(&self, packet: &RayPacket) -> Vec<Option<IntersecObj<'_>>> {
+        let hits = self
+            .accelerator
+            .closest_packet(packet, |i, p| self.objects[i].shape.intersec_packet(p));
+        hits[..packet.count()]
+            .iter()
+            .map(|hit| {
+                hit.map(|(i, distance)| IntersecObj {
+                    obj: &self.objects[i],
+                    distance,
+                })
+            })
+            .collect()
     }
 
-    fn intersec(&self, ray: &Ray) -> Option<Intersection> {
-        match self.intersec_obj(ray) {
-            None => None,
-            Some(obj) => Some(Intersection::new(obj, ray)),
-        }
+    fn intersec(&self, ray: &Ray) -> Option<Intersection<'_>> {
+        self.intersec_obj(ray)
+            .map(|obj| Intersection::new(obj, ray))
     }
 }
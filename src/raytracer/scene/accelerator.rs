@@ -0,0 +1,421 @@
+//! Search of the objects of the scene hit by a ray.
+//!
+//! Accelerators are built over the bounding boxes of the objects and test only objects
+//! whose boxes the ray goes through, the objects are tested by the given function.
+
+use crate::config::Accelerator as Kind;
+use crate::raytracer::packet::{RayPacket, LANES};
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{Aabb, Bvh};
+
+mod grid;
+mod kdtree;
+
+use grid::Grid;
+use kdtree::KdTree;
+
+/// Spatial structure over the primitives given by their bounding boxes
+pub trait Accelerator: Send + Sync {
+    /// Return the primitive hit by the ray nearest to its origin and the distance to the
+    /// hit, intersec returns the distance to the hit of the primitive
+    fn closest(&self, ray: &Ray, intersec: &dyn Fn(usize) -> Option<f32>) -> Option<(usize, f32)>;
+
+    /// Check if any primitive is hit by the ray not farther than t_max
+    fn any_hit(&self, ray: &Ray, t_max: f32, intersec: &dyn Fn(usize) -> Option<f32>) -> bool;
+
+    /// Return the primitives hit by the rays of the packet nearest to their origins,
+    /// intersec returns distances to the hits of the primitive by the rays of the given
+    /// packet. By default the rays are traced one by one in packets of a single ray.
+    fn closest_packet(
+        &self,
+        packet: &RayPacket,
+        intersec: &dyn Fn(usize, &RayPacket) -> [Option<f32>; LANES],
+    ) -> [Option<(usize, f32)>; LANES] {
+        std::array::from_fn(|l| {
+            let ray = packet.rays().get(l)?;
+            let single = RayPacket::new(vec![Ray::new(
+                ray.get_orig().clone(),
+                ray.get_dir().clone(),
+            )]);
+            self.closest(ray, &|i| intersec(i, &single)[0])
+        })
+    }
+}
+
+/// Keep the hits of the primitive which are nearer than the found ones
+fn update_nearest(
+    nearest: &mut [Option<(usize, f32)>; LANES],
+    i: usize,
+    hits: [Option<f32>; LANES],
+) {
+    for (nearest, t) in nearest.iter_mut().zip(hits) {
+        match t {
+            Some(t) if nearest.is_none_or(|(_, nearest)| t < nearest) => *nearest = Some((i, t)),
+            _ => (),
+        }
+    }
+}
+
+/// Every primitive is tested by every ray
+struct Linear {
+    count: usize,
+}
+
+impl Accelerator for Linear {
+    fn closest(&self, _ray: &Ray, intersec: &dyn Fn(usize) -> Option<f32>) -> Option<(usize, f32)> {
+        let mut nearest: Option<(usize, f32)> = None;
+        for i in 0..self.count {
+            if let Some(t) = intersec(i) {
+                if nearest.is_none_or(|(_, nearest)| t < nearest) {
+                    nearest = Some((i, t));
+                }
+            }
+        }
+        nearest
+    }
+
+    fn any_hit(&self, _ray: &Ray, t_max: f32, intersec: &dyn Fn(usize) -> Option<f32>) -> bool {
+        (0..self.count).any(|i| intersec(i).is_some_and(|t| t <= t_max))
+    }
+
+    fn closest_packet(
+        &self,
+        packet: &RayPacket,
+        intersec: &dyn Fn(usize, &RayPacket) -> [Option<f32>; LANES],
+    ) -> [Option<(usize, f32)>; LANES] {
+        let mut nearest = [None; LANES];
+        for i in 0..self.count {
+            update_nearest(&mut nearest, i, intersec(i, packet));
+        }
+        nearest
+    }
+}
+
+impl Accelerator for Bvh {
+    fn closest(&self, ray: &Ray, intersec: &dyn Fn(usize) -> Option<f32>) -> Option<(usize, f32)> {
+        Bvh::closest(self, ray, intersec)
+    }
+
+    fn any_hit(&self, ray: &Ray, t_max: f32, intersec: &dyn Fn(usize) -> Option<f32>) -> bool {
+        Bvh::any_hit(self, ray, t_max, intersec)
+    }
+
+    fn closest_packet(
+        &self,
+        packet: &RayPacket,
+        intersec: &dyn Fn(usize, &RayPacket) -> [Option<f32>; LANES],
+    ) -> [Option<(usize, f32)>; LANES] {
+        Bvh::closest_packet(self, packet, |i| intersec(i, packet))
+    }
+}
+
+/// Return the accelerator of the kind built over the primitives
+pub fn new_accelerator(kind: Kind, bounds: &[Aabb]) -> Box<dyn Accelerator> {
+    match kind {
+        Kind::BruteForce => Box::new(Linear {
+            count: bounds.len(),
+        }),
+        Kind::Bvh => Box::new(Bvh::new(bounds)),
+        Kind::Grid => Box::new(Grid::new(bounds)),
+        Kind::KdTree => Box::new(KdTree::new(bounds)),
+    }
+}
+
+/// Accelerator over the objects of the scene, unbounded objects (planes) are kept aside and
+/// tested by every ray
+pub struct ObjectAccelerator {
+    accelerator: Box<dyn Accelerator>,
+    /// objects by the primitives of the accelerator
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+impl ObjectAccelerator {
+    /// Build the accelerator over the objects by their bounding boxes
+    pub fn new(kind: Kind, bounds: Vec<Option<Aabb>>) -> ObjectAccelerator {
+        let mut boxes = Vec::new();
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (i, b) in bounds.into_iter().enumerate() {
+            match b {
+                Some(b) => {
+                    boxes.push(b);
+                    bounded.push(i);
+                }
+                None => unbounded.push(i),
+            }
+        }
+        ObjectAccelerator {
+            accelerator: new_accelerator(kind, &boxes),
+            bounded,
+            unbounded,
+        }
+    }
+
+    /// Return the object nearest to the ray origin and the distance to it, intersec returns
+    /// the distance to the object
+    pub fn closest<F: Fn(usize) -> Option<f32>>(
+        &self,
+        ray: &Ray,
+        intersec: F,
+    ) -> Option<(usize, f32)> {
+        let mut nearest = self
+            .accelerator
+            .closest(ray, &|i| intersec(self.bounded[i]))
+            .map(|(i, t)| (self.bounded[i], t));
+        for &i in self.unbounded.iter() {
+            if let Some(t) = intersec(i) {
+                if nearest.is_none_or(|(_, nearest)| t < nearest) {
+                    nearest = Some((i, t));
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Return the objects nearest to the origins of the rays of the packet and the distances
+    /// to them, intersec returns distances to the object by the rays of the given packet
+    pub fn closest_packet<F: Fn(usize, &RayPacket) -> [Option<f32>; LANES]>(
+        &self,
+        packet: &RayPacket,
+        intersec: F,
+    ) -> [Option<(usize, f32)>; LANES] {
+        let mut nearest = self
+            .accelerator
+            .closest_packet(packet, &|i, p| intersec(self.bounded[i], p))
+            .map(|hit| hit.map(|(i, t)| (self.bounded[i], t)));
+        for &i in self.unbounded.iter() {
+            update_nearest(&mut nearest, i, intersec(i, packet));
+        }
+        nearest
+    }
+
+    /// Check if any object is hit by the ray not farther than t_max
+    pub fn any_hit<F: Fn(usize) -> Option<f32>>(&self, ray: &Ray, t_max: f32, intersec: F) -> bool {
+        self.unbounded
+            .iter()
+            .any(|i| intersec(*i).is_some_and(|t| t <= t_max))
+            || self
+                .accelerator
+                .any_hit(ray, t_max, &|i| intersec(self.bounded[i]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Instant;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use crate::config::Accelerator as Kind;
+    use crate::raytracer::packet::{RayPacket, LANES};
+    use crate::raytracer::ray::Ray;
+    use crate::raytracer::scene::accelerator::new_accelerator;
+    use crate::raytracer::scene::shape::{new_aabox, new_sphere, Aabb, Shape};
+    use crate::raytracer::vector::Vector;
+
+    /// Return the distance to the middle of the part of the ray inside the box, so hits lie
+    /// deep in the space of the accelerators
+    fn middle(b: &Aabb, ray: &Ray) -> Option<f32> {
+        let (orig, dir) = (ray.get_orig().to_arr(), ray.get_dir().to_arr());
+        let (mut t_near, mut t_far) = (0.0_f32, f32::INFINITY);
+        for i in 0..3 {
+            let t1 = (b.min[i] - orig[i]) / dir[i];
+            let t2 = (b.max[i] - orig[i]) / dir[i];
+            t_near = t_near.max(t1.min(t2));
+            t_far = t_far.min(t1.max(t2));
+        }
+        (t_near <= t_far).then_some(0.5 * (t_near + t_far))
+    }
+
+    #[test]
+    fn every_kind_finds_same_hits_as_brute_force() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut v = |k: f32| {
+            Vector::new(rng.gen(), rng.gen(), rng.gen())
+                .to_arr()
+                .map(|c| k * c)
+        };
+        let boxes: Vec<Aabb> = (0..1000)
+            .map(|_| {
+                let (p, s) = (v(20.0), v(3.0));
+                Aabb {
+                    min: p,
+                    max: [0, 1, 2].map(|i| p[i] + s[i]),
+                }
+            })
+            .collect();
+        let rays: Vec<Ray> = (0..200)
+            .map(|_| {
+                Ray::new(
+                    Vector::new_from_arr(&v(30.0).map(|c| c - 5.0)),
+                    Vector::new_from_arr(&v(1.0).map(|c| c - 0.5)).norm(),
+                )
+            })
+            .collect();
+        let brute_force = new_accelerator(Kind::BruteForce, &boxes);
+
+        for kind in [Kind::Bvh, Kind::Grid, Kind::KdTree] {
+            let accelerator = new_accelerator(kind, &boxes);
+            for ray in rays.iter() {
+                let intersec = |i: usize| middle(&boxes[i], ray);
+                let expected = brute_force.closest(ray, &intersec);
+                let found = accelerator.closest(ray, &intersec);
+                assert_eq!(found.map(|(_, t)| t), expected.map(|(_, t)| t), "{kind:?}");
+
+                let t_max = expected.map_or(1.0, |(_, t)| t + 0.5);
+                let hit = accelerator.any_hit(ray, t_max, &intersec);
+                assert_eq!(hit, expected.is_some(), "{kind:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn grid_tests_every_primitive_once_per_ray() {
+        // long boxes crossing many cells of the grid
+        let boxes: Vec<Aabb> = (0..20)
+            .map(|k| {
+                let c = k as f32;
+                Aabb {
+                    min: [0.0, c, c],
+                    max: [20.0, c + 1.0, c + 1.0],
+                }
+            })
+            .collect();
+        let grid = new_accelerator(Kind::Grid, &boxes);
+        let ray = Ray::new(
+            Vector::new(-1.0, 0.5, 0.5),
+            Vector::new(1.0, 0.5, 0.5).norm(),
+        );
+        let tests = vec![Cell::new(0); boxes.len()];
+        // misses make the ray walk through all the cells
+        let intersec = |i: usize| {
+            tests[i].set(tests[i].get() + 1);
+            None
+        };
+        assert!(grid.closest(&ray, &intersec).is_none());
+        assert!(!grid.any_hit(&ray, f32::INFINITY, &intersec));
+        // once by closest and once by any_hit
+        assert!(tests.iter().all(|t| t.get() <= 2));
+        assert!(tests.iter().any(|t| t.get() == 2));
+    }
+
+    /// Shape made at the center with the size
+    type NewShape = fn(Vector, f32) -> Box<dyn Shape>;
+
+    fn sphere(c: Vector, r: f32) -> Box<dyn Shape> {
+        Box::new(new_sphere(c, r))
+    }
+
+    fn aabox(c: Vector, r: f32) -> Box<dyn Shape> {
+        let half = Vector::new(r, r, r);
+        Box::new(new_aabox(&c - &half, &c + &half))
+    }
+
+    /// Return shapes spread over the cube and the rays of the camera looking at them row
+    /// by row
+    fn shapes_and_camera(
+        new_shape: NewShape,
+        count: usize,
+        resolution: usize,
+    ) -> (Vec<Box<dyn Shape>>, Vec<Ray>) {
+        let mut rng = StdRng::seed_from_u64(3);
+        let shapes = (0..count)
+            .map(|_| {
+                let c = Vector::new(rng.gen(), rng.gen(), rng.gen());
+                let c = &(&c * 20.0) + &Vector::new(-10.0, -10.0, 10.0);
+                new_shape(c, rng.gen_range(0.1..0.5))
+            })
+            .collect();
+        let step = 1.0 / resolution as f32;
+        let rays = (0..resolution * resolution)
+            .map(|p| {
+                let (i, j) = ((p % resolution) as f32, (p / resolution) as f32);
+                let dir = Vector::new(i * step - 0.5, 0.5 - j * step, 0.5);
+                Ray::new(Vector::new(0.0, 0.0, 0.0), dir.norm())
+            })
+            .collect();
+        (shapes, rays)
+    }
+
+    fn packets(rays: &[Ray]) -> Vec<RayPacket> {
+        rays.chunks(LANES)
+            .map(|c| {
+                let c = c
+                    .iter()
+                    .map(|r| Ray::new(r.get_orig().clone(), r.get_dir().clone()))
+                    .collect();
+                RayPacket::new(c)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packets_find_same_hits_as_single_rays() {
+        for new_shape in [sphere as NewShape, aabox] {
+            let (shapes, rays) = shapes_and_camera(new_shape, 300, 30);
+            let bounds: Vec<Aabb> = shapes.iter().filter_map(|s| s.bounds()).collect();
+            // the last packet has free lanes
+            let rays = &rays[..rays.len() - 1];
+
+            for kind in [Kind::BruteForce, Kind::Bvh, Kind::Grid, Kind::KdTree] {
+                let accelerator = new_accelerator(kind, &bounds);
+                for (packet, rays) in packets(rays).iter().zip(rays.chunks(LANES)) {
+                    let found =
+                        accelerator.closest_packet(packet, &|i, p| shapes[i].intersec_packet(p));
+                    for (l, ray) in rays.iter().enumerate() {
+                        let intersec = |i: usize| shapes[i].intersec(ray).get_closer();
+                        assert_eq!(found[l], accelerator.closest(ray, &intersec), "{kind:?}");
+                    }
+                    assert!(found[rays.len()..].iter().all(Option::is_none));
+                }
+            }
+        }
+    }
+
+    /// Run by `cargo test --release -- --ignored --nocapture packet_benchmark`
+    #[test]
+    #[ignore]
+    fn packet_benchmark() {
+        for (name, new_shape) in [("spheres", sphere as NewShape), ("boxes", aabox)] {
+            let (shapes, rays) = shapes_and_camera(new_shape, 10_000, 1024);
+            let bounds: Vec<Aabb> = shapes.iter().filter_map(|s| s.bounds()).collect();
+            let bvh = new_accelerator(Kind::Bvh, &bounds);
+            let packets = packets(&rays);
+
+            let start = Instant::now();
+            let scalar = rays
+                .iter()
+                .filter(|ray| {
+                    let intersec = |i: usize| shapes[i].intersec(ray).get_closer();
+                    bvh.closest(ray, &intersec).is_some()
+                })
+                .count();
+            let scalar_time = start.elapsed();
+
+            let start = Instant::now();
+            let packet = packets
+                .iter()
+                .map(|packet| {
+                    let found = bvh.closest_packet(packet, &|i, p| shapes[i].intersec_packet(p));
+                    found.iter().filter(|hit| hit.is_some()).count()
+                })
+                .sum::<usize>();
+            let packet_time = start.elapsed();
+
+            assert_eq!(scalar, packet);
+            println!(
+                "{}: {} rays, {} hits: scalar {:?}, packets of {} {:?}, speedup {:.2}",
+                name,
+                rays.len(),
+                scalar,
+                scalar_time,
+                LANES,
+                packet_time,
+                scalar_time.as_secs_f64() / packet_time.as_secs_f64()
+            );
+        }
+    }
+}
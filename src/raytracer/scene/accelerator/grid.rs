@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::accelerator::Accelerator;
+use crate::raytracer::scene::shape::Aabb;
+
+/// Uniform grid of cells over the box of the scene, every cell keeps the primitives whose
+/// boxes overlap it. The ray walks the cells it crosses in order (3D-DDA), so it's good for
+/// many small primitives spread evenly over the scene.
+///
+/// Primitives overlapping several cells are tested by the ray once (mailboxing), the query
+/// remembers the ones it has tested.
+pub struct Grid {
+    bounds: Aabb,
+    /// number of the cells along the axes
+    dims: [usize; 3],
+    cell_size: [f32; 3],
+    /// primitives of the cell i are items[starts[i]..starts[i + 1]]
+    starts: Vec<usize>,
+    items: Vec<usize>,
+    /// primitives overlapping more than one cell
+    shared: Vec<bool>,
+}
+
+/// Number of the cells per primitive
+const DENSITY: f32 = 2.0;
+/// Max number of the cells along an axis
+const MAX_DIM: usize = 128;
+
+impl Grid {
+    pub fn new(bounds: &[Aabb]) -> Grid {
+        // boxes without points belong to shapes without surface
+        let bounds: Vec<(usize, &Aabb)> = bounds
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| !b.is_empty())
+            .collect();
+        let grid_bounds = bounds.iter().fold(Aabb::empty(), |g, (_, b)| g.union(b));
+        if bounds.is_empty() {
+            return Grid {
+                bounds: grid_bounds,
+                dims: [0; 3],
+                cell_size: [0.0; 3],
+                starts: vec![0],
+                items: Vec::new(),
+                shared: Vec::new(),
+            };
+        }
+
+        // cells are close to cubes, flat scenes get one cell across
+        let extent = [0, 1, 2].map(|i| grid_bounds.max[i] - grid_bounds.min[i]);
+        let volume: f32 = extent.iter().filter(|e| **e > 0.0).product();
+        let flat_axes = extent.iter().filter(|e| **e <= 0.0).count() as i32;
+        let k = (DENSITY * bounds.len() as f32 / volume).powf(1.0 / (3 - flat_axes).max(1) as f32);
+        let dims = extent.map(|e| ((e * k).round() as usize).clamp(1, MAX_DIM));
+        let cell_size = [0, 1, 2].map(|i| extent[i] / dims[i] as f32);
+
+        let mut grid = Grid {
+            bounds: grid_bounds,
+            dims,
+            cell_size,
+            starts: Vec::new(),
+            items: Vec::new(),
+            shared: Vec::new(),
+        };
+        // count the primitives of the cells first, so they are stored in one list
+        let mut counts = vec![0; dims[0] * dims[1] * dims[2] + 1];
+        for (_, b) in bounds.iter() {
+            grid.for_cells(b, |cell| counts[cell + 1] += 1);
+        }
+        for i in 1..counts.len() {
+            counts[i] += counts[i - 1];
+        }
+        let mut fill = counts.clone();
+        let mut items = vec![0; counts[counts.len() - 1]];
+        let mut shared = vec![false; bounds.last().map_or(0, |(i, _)| i + 1)];
+        for (i, b) in bounds.iter() {
+            let mut cells = 0;
+            grid.for_cells(b, |cell| {
+                items[fill[cell]] = *i;
+                fill[cell] += 1;
+                cells += 1;
+            });
+            shared[*i] = cells > 1;
+        }
+        grid.starts = counts;
+        grid.items = items;
+        grid.shared = shared;
+        grid
+    }
+
+    /// Return the cell of the coordinate along the axis, coordinates outside the grid go
+    /// to the border cells
+    fn cell_of(&self, axis: usize, c: f32) -> usize {
+        let k = (c - self.bounds.min[axis]) / self.cell_size[axis];
+        if k.is_nan() {
+            return 0;
+        }
+        (k.max(0.0) as usize).min(self.dims[axis] - 1)
+    }
+
+    fn index(&self, cell: [usize; 3]) -> usize {
+        (cell[2] * self.dims[1] + cell[1]) * self.dims[0] + cell[0]
+    }
+
+    fn for_cells<F: FnMut(usize)>(&self, b: &Aabb, mut f: F) {
+        let min = [0, 1, 2].map(|i| self.cell_of(i, b.min[i]));
+        let max = [0, 1, 2].map(|i| self.cell_of(i, b.max[i]));
+        for z in min[2]..=max[2] {
+            for y in min[1]..=max[1] {
+                for x in min[0]..=max[0] {
+                    f(self.index([x, y, z]));
+                }
+            }
+        }
+    }
+
+    /// Visit the cells crossed by the ray in order, visit gets primitives of the cell and
+    /// the distance where the ray leaves it and returns true to stop
+    fn walk<F: FnMut(&[usize], f32) -> bool>(&self, ray: &Ray, t_max: f32, mut visit: F) {
+        if self.items.is_empty() {
+            return;
+        }
+        let orig = ray.get_orig().to_arr();
+        let dir = ray.get_dir().to_arr();
+        let inv_dir = dir.map(|d| 1.0 / d);
+        let (t_enter, t_exit) = match self.bounds.clip(&orig, &inv_dir, t_max) {
+            Some(t) => t,
+            None => return,
+        };
+
+        let mut cell = [0, 1, 2].map(|i| self.cell_of(i, orig[i] + dir[i] * t_enter));
+        let step = dir.map(|d| if d > 0.0 { 1 } else { -1 });
+        // distances to the next borders of the cells along the axes and between the borders
+        let mut t_next = [0, 1, 2].map(|i| {
+            if dir[i] == 0.0 {
+                return f32::INFINITY;
+            }
+            let border = cell[i] + usize::from(dir[i] > 0.0);
+            let c = self.bounds.min[i] + border as f32 * self.cell_size[i];
+            (c - orig[i]) * inv_dir[i]
+        });
+        let t_delta = [0, 1, 2].map(|i| (self.cell_size[i] * inv_dir[i]).abs());
+
+        loop {
+            let axis = (0..3)
+                .min_by(|&a, &b| t_next[a].total_cmp(&t_next[b]))
+                .unwrap_or(0);
+            let t_leave = t_next[axis].min(t_exit);
+            let i = self.index(cell);
+            if visit(&self.items[self.starts[i]..self.starts[i + 1]], t_leave) {
+                return;
+            }
+            if t_next[axis] > t_exit {
+                return;
+            }
+            let next = cell[axis] as isize + step[axis];
+            if next < 0 || next >= self.dims[axis] as isize {
+                return;
+            }
+            cell[axis] = next as usize;
+            t_next[axis] += t_delta[axis];
+        }
+    }
+}
+
+impl Accelerator for Grid {
+    fn closest(&self, ray: &Ray, intersec: &dyn Fn(usize) -> Option<f32>) -> Option<(usize, f32)> {
+        let mut nearest: Option<(usize, f32)> = None;
+        let mut tested = HashSet::new();
+        self.walk(ray, f32::INFINITY, |items, t_leave| {
+            for &i in items {
+                if self.shared[i] && !tested.insert(i) {
+                    continue;
+                }
+                if let Some(t) = intersec(i) {
+                    if nearest.is_none_or(|(_, nearest)| t < nearest) {
+                        nearest = Some((i, t));
+                    }
+                }
+            }
+            // primitives of the next cells can't be closer than the found hit
+            nearest.is_some_and(|(_, t)| t <= t_leave)
+        });
+        nearest
+    }
+
+    fn any_hit(&self, ray: &Ray, t_max: f32, intersec: &dyn Fn(usize) -> Option<f32>) -> bool {
+        let mut found = false;
+        let mut tested = HashSet::new();
+        self.walk(ray, t_max, |items, _| {
+            found = items
+                .iter()
+                .filter(|i| !self.shared[**i] || tested.insert(**i))
+                .any(|i| intersec(*i).is_some_and(|t| t <= t_max));
+            found
+        });
+        found
+    }
+}
@@ -0,0 +1,243 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::accelerator::Accelerator;
+use crate::raytracer::scene::shape::Aabb;
+
+/// Tree of the space split by planes orthogonal to the coordinate axes, primitives crossing
+/// the plane go to both sides. The planes are chosen by the surface area heuristic, empty
+/// space is cut off, so the ray visits leaves in order and stops at the first hit.
+pub struct KdTree {
+    bounds: Aabb,
+    nodes: Vec<Node>,
+    /// primitives of the leaves
+    items: Vec<usize>,
+}
+
+enum Node {
+    /// primitives from start to start + count in the list of items
+    Leaf { start: usize, count: usize },
+    /// the child below the plane goes right after its parent
+    Inner {
+        axis: usize,
+        split: f32,
+        above: usize,
+    },
+}
+
+/// Cost of the visit of the inner node relative to the intersection of a primitive
+const TRAVERSAL_COST: f32 = 1.0;
+const INTERSEC_COST: f32 = 1.5;
+/// Discount of the split cutting off the empty space
+const EMPTY_BONUS: f32 = 0.5;
+/// Number of the splits worse than the leaf allowed on the way from the root
+const MAX_BAD_SPLITS: usize = 3;
+
+/// Start or end of the primitive box along the axis
+#[derive(Clone, Copy)]
+struct Edge {
+    t: f32,
+    is_start: bool,
+}
+
+impl KdTree {
+    pub fn new(bounds: &[Aabb]) -> KdTree {
+        // boxes without points belong to shapes without surface
+        let primitives: Vec<usize> = (0..bounds.len())
+            .filter(|i| !bounds[*i].is_empty())
+            .collect();
+        let mut tree = KdTree {
+            bounds: primitives
+                .iter()
+                .fold(Aabb::empty(), |b, i| b.union(&bounds[*i])),
+            nodes: Vec::new(),
+            items: Vec::new(),
+        };
+        if !primitives.is_empty() {
+            let depth = 8 + (1.3 * (primitives.len() as f32).log2()) as usize;
+            let node_bounds = tree.bounds.clone();
+            tree.build(bounds, primitives, &node_bounds, depth, 0);
+        }
+        tree
+    }
+
+    fn build(
+        &mut self,
+        bounds: &[Aabb],
+        primitives: Vec<usize>,
+        node: &Aabb,
+        depth: usize,
+        bad_splits: usize,
+    ) {
+        let count = primitives.len();
+        let leaf_cost = INTERSEC_COST * count as f32;
+        let best = if count <= 1 || depth == 0 {
+            None
+        } else {
+            best_split(bounds, &primitives, node)
+        };
+        let (cost, axis, split) = match best {
+            Some(best) => best,
+            None => return self.push_leaf(primitives),
+        };
+        let bad_splits = bad_splits + usize::from(cost > leaf_cost);
+        if (cost > 4.0 * leaf_cost && count < 16) || bad_splits >= MAX_BAD_SPLITS {
+            return self.push_leaf(primitives);
+        }
+
+        // primitives lying in the plane go above
+        let below: Vec<usize> = primitives
+            .iter()
+            .copied()
+            .filter(|i| bounds[*i].min[axis] < split)
+            .collect();
+        let above: Vec<usize> = primitives
+            .into_iter()
+            .filter(|i| bounds[*i].max[axis] >= split)
+            .collect();
+        let (mut node_below, mut node_above) = (node.clone(), node.clone());
+        node_below.max[axis] = split;
+        node_above.min[axis] = split;
+
+        let n = self.nodes.len();
+        self.nodes.push(Node::Inner {
+            axis,
+            split,
+            above: 0,
+        });
+        self.build(bounds, below, &node_below, depth - 1, bad_splits);
+        let above_node = self.nodes.len();
+        if let Node::Inner { above, .. } = &mut self.nodes[n] {
+            *above = above_node;
+        }
+        self.build(bounds, above, &node_above, depth - 1, bad_splits);
+    }
+
+    fn push_leaf(&mut self, primitives: Vec<usize>) {
+        self.nodes.push(Node::Leaf {
+            start: self.items.len(),
+            count: primitives.len(),
+        });
+        self.items.extend(primitives);
+    }
+
+    /// Visit the leaves crossed by the ray in order, visit gets primitives of the leaf and
+    /// the distance where the ray leaves it and returns true to stop
+    fn walk<F: FnMut(&[usize], f32) -> bool>(&self, ray: &Ray, t_max: f32, mut visit: F) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let orig = ray.get_orig().to_arr();
+        let inv_dir = ray.get_dir().to_arr().map(|d| 1.0 / d);
+        let (t_enter, t_exit) = match self.bounds.clip(&orig, &inv_dir, t_max) {
+            Some(t) => t,
+            None => return,
+        };
+
+        // nodes with the part of the ray inside them
+        let mut stack = vec![(0, t_enter, t_exit)];
+        while let Some((mut n, t_min, mut t_max)) = stack.pop() {
+            loop {
+                match &self.nodes[n] {
+                    Node::Leaf { start, count } => {
+                        if visit(&self.items[*start..start + count], t_max) {
+                            return;
+                        }
+                        break;
+                    }
+                    Node::Inner { axis, split, above } => {
+                        let t_split = (split - orig[*axis]) * inv_dir[*axis];
+                        let below_first = orig[*axis] < *split
+                            || (orig[*axis] == *split && inv_dir[*axis] <= 0.0);
+                        let (first, second) = if below_first {
+                            (n + 1, *above)
+                        } else {
+                            (*above, n + 1)
+                        };
+                        if t_split > t_max || t_split <= 0.0 {
+                            n = first;
+                        } else if t_split < t_min {
+                            n = second;
+                        } else {
+                            stack.push((second, t_split, t_max));
+                            n = first;
+                            t_max = t_split;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Return the cheapest split of the node: its cost, axis and position
+fn best_split(bounds: &[Aabb], primitives: &[usize], node: &Aabb) -> Option<(f32, usize, f32)> {
+    let area = node.half_area();
+    let mut best: Option<(f32, usize, f32)> = None;
+    for axis in 0..3 {
+        let mut edges: Vec<Edge> = primitives
+            .iter()
+            .flat_map(|i| {
+                let b = &bounds[*i];
+                [(b.min[axis], true), (b.max[axis], false)]
+                    .map(|(t, is_start)| Edge { t, is_start })
+            })
+            .collect();
+        // starts go before ends at the same position, so flat boxes in the plane go above
+        edges.sort_by(|a, b| a.t.total_cmp(&b.t).then(b.is_start.cmp(&a.is_start)));
+
+        let (mut below, mut above) = (0, primitives.len());
+        for e in edges.iter() {
+            if !e.is_start {
+                above -= 1;
+            }
+            if node.min[axis] < e.t && e.t < node.max[axis] {
+                let (mut b, mut a) = (node.clone(), node.clone());
+                b.max[axis] = e.t;
+                a.min[axis] = e.t;
+                let bonus = if below == 0 || above == 0 {
+                    EMPTY_BONUS
+                } else {
+                    0.0
+                };
+                let hits = (b.half_area() * below as f32 + a.half_area() * above as f32) / area;
+                let cost = TRAVERSAL_COST + INTERSEC_COST * (1.0 - bonus) * hits;
+                if best.is_none_or(|(c, _, _)| cost < c) {
+                    best = Some((cost, axis, e.t));
+                }
+            }
+            if e.is_start {
+                below += 1;
+            }
+        }
+    }
+    // the split of the flat node has no area to weigh the sides
+    best.filter(|_| area > 0.0)
+}
+
+impl Accelerator for KdTree {
+    fn closest(&self, ray: &Ray, intersec: &dyn Fn(usize) -> Option<f32>) -> Option<(usize, f32)> {
+        let mut nearest: Option<(usize, f32)> = None;
+        self.walk(ray, f32::INFINITY, |items, t_leave| {
+            for &i in items {
+                if let Some(t) = intersec(i) {
+                    if nearest.is_none_or(|(_, nearest)| t < nearest) {
+                        nearest = Some((i, t));
+                    }
+                }
+            }
+            // primitives of the next leaves can't be closer than the found hit
+            nearest.is_some_and(|(_, t)| t <= t_leave)
+        });
+        nearest
+    }
+
+    fn any_hit(&self, ray: &Ray, t_max: f32, intersec: &dyn Fn(usize) -> Option<f32>) -> bool {
+        let mut found = false;
+        self.walk(ray, t_max, |items, _| {
+            found = items
+                .iter()
+                .any(|i| intersec(*i).is_some_and(|t| t <= t_max));
+            found
+        });
+        found
+    }
+}
@@ -0,0 +1,391 @@
+//! Creation of the shapes from their configuration.
+
+use crate::config::{
+    BezierGeometry, BoxGeometry, ConeGeometry, CsgGeometry, CsgOperation, CurveStyle,
+    CurvesGeometry, CylinderGeometry, DiskGeometry, FieldConfig, HeightfieldGeometry, MeshGeometry,
+    MetaballsGeometry, OrientedBoxGeometry, PlaneGeometry, QuadGeometry, QuadricGeometry,
+    QuadricSurface, SdfGeometry, ShapeConfig, SphereGeometry, SubdivisionScheme, TorusGeometry,
+    TransformConfig, TransformedGeometry, TriangleGeometry,
+};
+use crate::raytracer::matrix::Matrix;
+use crate::raytracer::scene::loader::{self, LoadError};
+use crate::raytracer::scene::shape::{self, Shape};
+use crate::raytracer::vector::Vector;
+
+pub trait Geometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError>;
+}
+
+impl Geometry for ShapeConfig {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        match self {
+            ShapeConfig::Sphere(g) => g.new_shape(),
+            ShapeConfig::Plane(g) => g.new_shape(),
+            ShapeConfig::AaBox(g) => g.new_shape(),
+            ShapeConfig::OrientedBox(g) => g.new_shape(),
+            ShapeConfig::Cylinder(g) => g.new_shape(),
+            ShapeConfig::Cone(g) => g.new_shape(),
+            ShapeConfig::Torus(g) => g.new_shape(),
+            ShapeConfig::Disk(g) => g.new_shape(),
+            ShapeConfig::Quad(g) => g.new_shape(),
+            ShapeConfig::Triangle(g) => g.new_shape(),
+            ShapeConfig::Mesh(g) => g.new_shape(),
+            ShapeConfig::Csg(g) => g.new_shape(),
+            ShapeConfig::Sdf(g) => g.new_shape(),
+            ShapeConfig::Heightfield(g) => g.new_shape(),
+            ShapeConfig::Metaballs(g) => g.new_shape(),
+            ShapeConfig::BezierPatches(g) => g.new_shape(),
+            ShapeConfig::Quadric(g) => g.new_shape(),
+            ShapeConfig::Curves(g) => g.new_shape(),
+            ShapeConfig::Transformed(g) => g.new_shape(),
+        }
+    }
+}
+
+impl Geometry for SphereGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        Ok(Box::new(shape::new_sphere(
+            Vector::new_from_arr(&self.center),
+            self.radius,
+        )))
+    }
+}
+
+impl Geometry for PlaneGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        Ok(Box::new(shape::new_plane(
+            Vector::new_from_arr(&self.point),
+            Vector::new_from_arr(&self.normal),
+        )))
+    }
+}
+
+impl Geometry for BoxGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        Ok(Box::new(shape::new_aabox(
+            Vector::new_from_arr(&self.min),
+            Vector::new_from_arr(&self.max),
+        )))
+    }
+}
+
+impl Geometry for OrientedBoxGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        Ok(Box::new(shape::new_oriented_box(
+            Vector::new_from_arr(&self.center),
+            self.half_extents,
+            self.rotation,
+        )))
+    }
+}
+
+/// Check that the ends of the axis differ, otherwise the axis has no direction
+fn check_axis(name: &str, start: &[f32; 3], end: &[f32; 3]) -> Result<(), LoadError> {
+    if start == end {
+        return Err(LoadError::new(
+            name,
+            None,
+            "start and end of the axis are the same point".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+impl Geometry for CylinderGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        check_axis("cylinder", &self.start, &self.end)?;
+        Ok(Box::new(shape::new_cylinder(
+            Vector::new_from_arr(&self.start),
+            Vector::new_from_arr(&self.end),
+            self.radius,
+            self.caps.unwrap_or(true),
+        )))
+    }
+}
+
+impl Geometry for ConeGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        check_axis("cone", &self.start, &self.end)?;
+        Ok(Box::new(shape::new_cone(
+            Vector::new_from_arr(&self.start),
+            Vector::new_from_arr(&self.end),
+            self.start_radius,
+            self.end_radius,
+            self.caps.unwrap_or(true),
+        )))
+    }
+}
+
+impl Geometry for TorusGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        Ok(Box::new(shape::new_torus(
+            Vector::new_from_arr(&self.center),
+            Vector::new_from_arr(&self.axis),
+            self.major_radius,
+            self.minor_radius,
+        )))
+    }
+}
+
+impl Geometry for DiskGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        Ok(Box::new(shape::new_disk(
+            Vector::new_from_arr(&self.center),
+            Vector::new_from_arr(&self.normal),
+            self.radius,
+        )))
+    }
+}
+
+impl Geometry for QuadGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        Ok(Box::new(shape::new_quad(
+            Vector::new_from_arr(&self.corner),
+            Vector::new_from_arr(&self.edge_u),
+            Vector::new_from_arr(&self.edge_v),
+        )))
+    }
+}
+
+impl Geometry for TriangleGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        let [a, b, c] = self.vertices.map(|v| Vector::new_from_arr(&v));
+        Ok(Box::new(shape::new_triangle(a, b, c)))
+    }
+}
+
+impl Geometry for MeshGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        let mut data = loader::load_mesh(&self.path)?;
+        if let Some(scale) = self.scale {
+            data.scale(scale);
+        }
+        if let Some(subdivision) = &self.subdivision {
+            let scheme = match subdivision.scheme {
+                SubdivisionScheme::Loop => shape::Scheme::Loop,
+                SubdivisionScheme::CatmullClark => shape::Scheme::CatmullClark,
+            };
+            data.subdivide(scheme, subdivision.levels, self.crease_angle);
+        }
+        // subdivided surface is smooth
+        if self.smooth_normals || self.subdivision.is_some() {
+            data.smooth_normals(self.crease_angle);
+        }
+        Ok(Box::new(shape::Mesh::new(data)))
+    }
+}
+
+impl Geometry for CsgGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        let operation = match self.operation {
+            CsgOperation::Union => shape::Operation::Union,
+            CsgOperation::Intersection => shape::Operation::Intersection,
+            CsgOperation::Difference => shape::Operation::Difference,
+        };
+        Ok(Box::new(shape::new_csg(
+            operation,
+            self.left.new_shape()?,
+            self.right.new_shape()?,
+        )))
+    }
+}
+
+impl Geometry for SdfGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        Ok(Box::new(shape::new_sdf(
+            new_field(&self.field),
+            self.max_steps.unwrap_or(128),
+            self.epsilon.unwrap_or(1e-4),
+            self.max_distance.unwrap_or(1000.0),
+        )))
+    }
+}
+
+impl Geometry for HeightfieldGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        let map = loader::load_height_map(&self.path)?;
+        Ok(Box::new(shape::new_heightfield(
+            map,
+            Vector::new_from_arr(&self.origin),
+            self.size,
+            self.height,
+        )))
+    }
+}
+
+impl Geometry for MetaballsGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        let balls = self
+            .balls
+            .iter()
+            .map(|b| shape::Ball {
+                center: Vector::new_from_arr(&b.center),
+                radius: b.radius,
+                weight: b.weight.unwrap_or(1.0),
+            })
+            .collect();
+        Ok(Box::new(shape::new_metaballs(balls, self.threshold)))
+    }
+}
+
+impl Geometry for BezierGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        let mut patches = match &self.path {
+            Some(path) => loader::load_patches(path)?,
+            None => Vec::new(),
+        };
+        patches.extend(self.patches.iter().map(|p| shape::Patch {
+            points: p.map(|c| Vector::new_from_arr(&c)),
+        }));
+        let data = shape::tessellate(&patches, self.tolerance.unwrap_or(0.01));
+        Ok(Box::new(shape::Mesh::new(data)))
+    }
+}
+
+impl Geometry for QuadricGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        let v = Vector::new_from_arr;
+        let quadric = match &self.surface {
+            QuadricSurface::Coefficients(c) => shape::new_quadric(*c),
+            QuadricSurface::Ellipsoid { center, radii } => shape::new_ellipsoid(v(center), *radii),
+            QuadricSurface::Paraboloid {
+                vertex,
+                axis,
+                focal_length,
+            } => shape::new_paraboloid(v(vertex), v(axis), *focal_length),
+            QuadricSurface::Hyperboloid {
+                center,
+                axis,
+                radius,
+                half_length,
+                two_sheets,
+            } => shape::new_hyperboloid(v(center), v(axis), *radius, *half_length, *two_sheets),
+        };
+        Ok(match &self.bounds {
+            None => Box::new(quadric),
+            Some(b) => Box::new(quadric.clip(v(&b.min), v(&b.max))),
+        })
+    }
+}
+
+impl Geometry for CurvesGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        let mut curves = match &self.path {
+            Some(path) => loader::load_curves(path)?,
+            None => Vec::new(),
+        };
+        curves.extend(self.curves.iter().map(|c| shape::Curve {
+            points: c.points.map(|p| Vector::new_from_arr(&p)),
+            width: c.width,
+        }));
+        let kind = match self.style {
+            CurveStyle::Ribbon => shape::CurveKind::Ribbon,
+            CurveStyle::Tube => shape::CurveKind::Tube,
+        };
+        Ok(Box::new(shape::new_curves(&curves, kind)))
+    }
+}
+
+impl Geometry for TransformedGeometry {
+    fn new_shape(&self) -> Result<Box<dyn Shape>, LoadError> {
+        transform(self.shape.new_shape()?, &self.transform)
+    }
+}
+
+/// Move the shape by the transformation from the configuration
+pub fn transform(
+    shape: Box<dyn Shape>,
+    cfg: &TransformConfig,
+) -> Result<Box<dyn Shape>, LoadError> {
+    let m = transform_matrix(cfg);
+    match shape::new_transformed(shape, &m) {
+        Some(t) => Ok(Box::new(t)),
+        None => Err(LoadError::new(
+            "transform",
+            None,
+            "transformation is singular (scale by zero)".to_string(),
+        )),
+    }
+}
+
+/// Return matrix of the transformation: scale, then rotation, then translation
+pub fn transform_matrix(cfg: &TransformConfig) -> Matrix {
+    let v = Vector::new_from_arr;
+    let mut m = Matrix::identity();
+    if let Some(scale) = &cfg.scale {
+        m = &Matrix::scale(&v(scale)) * &m;
+    }
+    if let Some(rotation) = &cfg.rotation {
+        m = &Matrix::rotation(rotation) * &m;
+    }
+    if let Some(r) = &cfg.axis_angle {
+        m = &Matrix::rotation_axis(&v(&r.axis), r.angle) * &m;
+    }
+    if let Some(translation) = &cfg.translation {
+        m = &Matrix::translation(&v(translation)) * &m;
+    }
+    m
+}
+
+fn new_field(cfg: &FieldConfig) -> shape::Field {
+    let v = Vector::new_from_arr;
+    let pair = |left: &FieldConfig, right: &FieldConfig| {
+        (Box::new(new_field(left)), Box::new(new_field(right)))
+    };
+    match cfg {
+        FieldConfig::Sphere { center, radius } => shape::Field::Sphere {
+            center: v(center),
+            radius: *radius,
+        },
+        FieldConfig::RoundBox {
+            center,
+            half_extents,
+            radius,
+        } => shape::Field::RoundBox {
+            center: v(center),
+            half_extents: v(half_extents),
+            radius: *radius,
+        },
+        FieldConfig::Torus {
+            center,
+            major_radius,
+            minor_radius,
+        } => shape::Field::Torus {
+            center: v(center),
+            major_radius: *major_radius,
+            minor_radius: *minor_radius,
+        },
+        FieldConfig::Capsule { start, end, radius } => shape::Field::Capsule {
+            start: v(start),
+            end: v(end),
+            radius: *radius,
+        },
+        FieldConfig::Mandelbulb {
+            center,
+            scale,
+            power,
+            iterations,
+        } => shape::Field::Mandelbulb {
+            center: v(center),
+            scale: *scale,
+            power: power.unwrap_or(8.0),
+            iterations: iterations.unwrap_or(8),
+        },
+        FieldConfig::Union { left, right } => {
+            let (a, b) = pair(left, right);
+            shape::Field::Union(a, b)
+        }
+        FieldConfig::Intersection { left, right } => {
+            let (a, b) = pair(left, right);
+            shape::Field::Intersection(a, b)
+        }
+        FieldConfig::Difference { left, right } => {
+            let (a, b) = pair(left, right);
+            shape::Field::Difference(a, b)
+        }
+        FieldConfig::SmoothUnion { left, right, k } => {
+            let (a, b) = pair(left, right);
+            shape::Field::SmoothUnion(a, b, *k)
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::raytracer::matrix::Matrix;
+use crate::raytracer::scene::shape::{Curve, HeightMap, MeshData, Patch};
+
+mod curves;
+mod gltf;
+mod obj;
+mod patch;
+mod ply;
+mod pnm;
+mod stl;
+
+pub use gltf::{GltfCamera, GltfScene, Material};
+
+/// Error of loading geometry of the scene from a file
+#[derive(Debug)]
+pub struct LoadError {
+    pub path: String,
+    /// Line of the file where the error was found (if the file is text)
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl LoadError {
+    pub(crate) fn new(path: &str, line: Option<usize>, message: String) -> LoadError {
+        LoadError {
+            path: path.to_string(),
+            line,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            None => write!(f, "{}: {}", self.path, self.message),
+            Some(line) => write!(f, "{}:{}: {}", self.path, line, self.message),
+        }
+    }
+}
+
+/// Load mesh from the file, format of the file is chosen by its extension
+pub fn load_mesh(path: &str) -> Result<MeshData, LoadError> {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match ext.as_deref() {
+        Some("obj") => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|err| LoadError::new(path, None, err.to_string()))?;
+            obj::parse(&text).map_err(|(line, message)| LoadError::new(path, Some(line), message))
+        }
+        Some("ply") => {
+            let bytes =
+                std::fs::read(path).map_err(|err| LoadError::new(path, None, err.to_string()))?;
+            ply::parse(&bytes).map_err(|(line, message)| LoadError::new(path, line, message))
+        }
+        Some("stl") => {
+            let bytes =
+                std::fs::read(path).map_err(|err| LoadError::new(path, None, err.to_string()))?;
+            stl::parse(&bytes).map_err(|(line, message)| LoadError::new(path, line, message))
+        }
+        _ => Err(LoadError::new(
+            path,
+            None,
+            "unknown mesh file format".to_string(),
+        )),
+    }
+}
+
+/// Load height map from the grayscale (PGM) or color (PPM) image
+pub fn load_height_map(path: &str) -> Result<HeightMap, LoadError> {
+    let bytes = std::fs::read(path).map_err(|err| LoadError::new(path, None, err.to_string()))?;
+    pnm::parse(&bytes).map_err(|message| LoadError::new(path, None, message))
+}
+
+/// Load Bézier patches from the file in the format of the Utah teapot data
+pub fn load_patches(path: &str) -> Result<Vec<Patch>, LoadError> {
+    let text =
+        std::fs::read_to_string(path).map_err(|err| LoadError::new(path, None, err.to_string()))?;
+    patch::parse(&text).map_err(|(line, message)| LoadError::new(path, Some(line), message))
+}
+
+/// Load cubic Bézier curves from the text file
+pub fn load_curves(path: &str) -> Result<Vec<Curve>, LoadError> {
+    let text =
+        std::fs::read_to_string(path).map_err(|err| LoadError::new(path, None, err.to_string()))?;
+    curves::parse(&text).map_err(|(line, message)| LoadError::new(path, Some(line), message))
+}
+
+/// Load the scene from glTF file (.gltf or .glb) moved by the root transform
+pub fn load_gltf(path: &str, root: &Matrix) -> Result<GltfScene, LoadError> {
+    let bytes = std::fs::read(path).map_err(|err| LoadError::new(path, None, err.to_string()))?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    gltf::parse(&bytes, dir, root).map_err(|message| LoadError::new(path, None, message))
+}
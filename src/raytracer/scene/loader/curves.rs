@@ -0,0 +1,61 @@
+//! Text file of curves, one cubic Bézier curve in a line: coordinates of 4 control points
+//! and the width at the start and at the end of the curve (one width for the whole curve).
+//! ```text
+//! # x0 y0 z0  x1 y1 z1  x2 y2 z2  x3 y3 z3  width0 width1
+//! 0 0 0  0 1 0  0.2 2 0  0.5 3 0  0.02 0.005
+//! ```
+
+use crate::raytracer::scene::shape::Curve;
+use crate::raytracer::vector::Vector;
+
+/// Parse text of the curves file, error is the line number and the message
+pub fn parse(text: &str) -> Result<Vec<Curve>, (usize, String)> {
+    let mut curves = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let numbers = line
+            .split_whitespace()
+            .map(|t| {
+                t.parse::<f32>()
+                    .map_err(|_| (i + 1, format!("invalid number '{}'", t)))
+            })
+            .collect::<Result<Vec<f32>, _>>()?;
+        let width = match numbers.len() {
+            0 => continue,
+            13 => [numbers[12], numbers[12]],
+            14 => [numbers[12], numbers[13]],
+            n => {
+                return Err((
+                    i + 1,
+                    format!("curve must have 13 or 14 numbers, found {}", n),
+                ))
+            }
+        };
+        curves.push(Curve {
+            points: [0, 1, 2, 3]
+                .map(|k| Vector::new(numbers[3 * k], numbers[3 * k + 1], numbers[3 * k + 2])),
+            width,
+        });
+    }
+    Ok(curves)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::scene::loader::curves::parse;
+
+    #[test]
+    fn width_is_optional_at_the_end() {
+        let text =
+            "# two curves\n0 0 0 0 1 0 0 2 0 0 3 0 0.1\n\n0 0 0 0 1 0 0 2 0 0 3 0 0.1 0.01\n";
+        let curves = parse(text).unwrap();
+        assert_eq!(curves.len(), 2);
+        assert_eq!(curves[0].width, [0.1, 0.1]);
+        assert_eq!(curves[1].width, [0.1, 0.01]);
+
+        assert_eq!(parse("0 0 0 1\n").err().unwrap().0, 1);
+    }
+}
@@ -0,0 +1,698 @@
+//! glTF 2.0 scenes: JSON (.gltf) with external or embedded (data URI) buffers and the binary
+//! container (.glb).
+//!
+//! Meshes are read once in their own coordinates and placed to the world by the transforms
+//! of the node hierarchy as many times as nodes use them, every primitive becomes a mesh with
+//! the base color, metallic and roughness factors of its material. The first camera met in the hierarchy and point and spot lights of the
+//! KHR_lights_punctual extension are read as well. Textures, animations, skins and morph
+//! targets are ignored.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::image::Color;
+use crate::raytracer::matrix::Matrix;
+use crate::raytracer::scene::shape::MeshData;
+use crate::raytracer::vector::Vector;
+
+/// Meshes, camera and lights of the glTF scene, meshes are in their own coordinates
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    /// Meshes (by their index) placed to the world by the transforms of the nodes
+    pub instances: Vec<(usize, Matrix)>,
+    pub camera: Option<GltfCamera>,
+    pub lights: Vec<Vector>,
+}
+
+pub struct GltfMesh {
+    pub data: MeshData,
+    pub material: Material,
+}
+
+pub struct GltfCamera {
+    pub origin: Vector,
+    pub view: Vector,
+    pub up: Vector,
+    /// vertical field of view in radians, None if the camera is orthographic
+    pub yfov: Option<f32>,
+}
+
+/// Metallic-roughness material, the color is in the linear color space
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Material {
+    pub base_color_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material {
+            base_color_factor: [1.0; 4],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+        }
+    }
+}
+
+impl Material {
+    /// Return the base color in sRGB
+    pub fn color(&self) -> Color {
+        let [r, g, b, _] = self.base_color_factor;
+        to_srgb([r, g, b])
+    }
+}
+
+/// Parse content of .gltf or .glb file, external buffers are looked for in the directory,
+/// the whole scene is moved by the root transform
+pub fn parse(bytes: &[u8], dir: &Path, root: &Matrix) -> Result<GltfScene, String> {
+    let (json, bin) = if bytes.starts_with(b"glTF") {
+        split_glb(bytes)?
+    } else {
+        (bytes, None)
+    };
+    let doc: Document =
+        serde_json::from_slice(json).map_err(|err| format!("invalid glTF JSON: {}", err))?;
+    let buffers = doc
+        .buffers
+        .iter()
+        .map(|b| load_buffer(b, dir, bin))
+        .collect::<Result<Vec<_>, String>>()?;
+    let reader = Reader {
+        doc: &doc,
+        buffers: &buffers,
+    };
+
+    let roots = match doc.scenes.get(doc.scene.unwrap_or(0)) {
+        Some(scene) => &scene.nodes,
+        None => return Err("file has no scenes".to_string()),
+    };
+    let mut gltf = GltfScene {
+        meshes: Vec::new(),
+        instances: Vec::new(),
+        camera: None,
+        lights: Vec::new(),
+    };
+    let mut visited = HashSet::new();
+    // primitives of the glTF meshes already read
+    let mut primitives: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut stack: Vec<(usize, Matrix)> = roots.iter().rev().map(|n| (*n, root.clone())).collect();
+    while let Some((index, parent)) = stack.pop() {
+        let node = doc
+            .nodes
+            .get(index)
+            .ok_or_else(|| format!("node {} doesn't exist", index))?;
+        if !visited.insert(index) {
+            return Err(format!("node {} is met twice in the hierarchy", index));
+        }
+        let m = &parent * &node.local_matrix();
+
+        if let Some(mesh) = node.mesh {
+            let read = match primitives.entry(mesh) {
+                Entry::Occupied(e) => e.into_mut(),
+                Entry::Vacant(e) => {
+                    let mesh = doc
+                        .meshes
+                        .get(mesh)
+                        .ok_or_else(|| format!("mesh {} doesn't exist", mesh))?;
+                    let mut read = Vec::with_capacity(mesh.primitives.len());
+                    for p in mesh.primitives.iter() {
+                        read.push(gltf.meshes.len());
+                        gltf.meshes.push(reader.primitive(p)?);
+                    }
+                    e.insert(read)
+                }
+            };
+            for i in read.iter() {
+                gltf.instances.push((*i, m.clone()));
+            }
+        }
+        if let (Some(camera), None) = (node.camera, &gltf.camera) {
+            let camera = doc
+                .cameras
+                .get(camera)
+                .ok_or_else(|| format!("camera {} doesn't exist", camera))?;
+            gltf.camera = Some(GltfCamera {
+                origin: m.point(&Vector::new(0.0, 0.0, 0.0)),
+                view: m.vector(&Vector::new(0.0, 0.0, -1.0)),
+                up: m.vector(&Vector::new(0.0, 1.0, 0.0)),
+                yfov: camera.perspective.as_ref().map(|p| p.yfov),
+            });
+        }
+        if let Some(light) = &node.extensions.lights {
+            let lights = doc
+                .extensions
+                .lights
+                .as_ref()
+                .map_or(&[][..], |l| &l.lights);
+            let kind = lights
+                .get(light.light)
+                .map(|l| l.kind.as_str())
+                .ok_or_else(|| format!("light {} doesn't exist", light.light))?;
+            // directional lights have no position
+            if kind == "point" || kind == "spot" {
+                gltf.lights.push(m.point(&Vector::new(0.0, 0.0, 0.0)));
+            }
+        }
+
+        stack.extend(node.children.iter().rev().map(|c| (*c, m.clone())));
+    }
+    Ok(gltf)
+}
+
+/// Return JSON and binary chunks of the .glb container
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    const JSON: u32 = 0x4E4F_534A;
+    const BIN: u32 = 0x004E_4942;
+
+    let word = |pos: usize| {
+        bytes
+            .get(pos..pos + 4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .ok_or_else(|| "unexpected end of file".to_string())
+    };
+    if word(4)? != 2 {
+        return Err(format!("unsupported glTF version {}", word(4)?));
+    }
+    let length = usize::min(word(8)? as usize, bytes.len());
+
+    let (mut json, mut bin) = (None, None);
+    let mut pos = 12;
+    while pos + 8 <= length {
+        let (size, kind) = (word(pos)? as usize, word(pos + 4)?);
+        let chunk = bytes
+            .get(pos + 8..pos + 8 + size)
+            .ok_or_else(|| "unexpected end of file".to_string())?;
+        match kind {
+            JSON if json.is_none() => json = Some(chunk),
+            BIN if bin.is_none() => bin = Some(chunk),
+            _ => {}
+        }
+        pos += 8 + size;
+    }
+    let json = json.ok_or_else(|| "file has no JSON chunk".to_string())?;
+    Ok((json, bin))
+}
+
+fn load_buffer(buffer: &Buffer, dir: &Path, bin: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let data = match &buffer.uri {
+        // buffer without uri refers to the binary chunk of .glb
+        None => bin
+            .ok_or_else(|| "buffer has no data".to_string())?
+            .to_vec(),
+        Some(uri) if uri.starts_with("data:") => {
+            let (_, data) = uri
+                .split_once(";base64,")
+                .ok_or_else(|| "data URI of the buffer isn't base64".to_string())?;
+            decode_base64(data)?
+        }
+        Some(uri) => {
+            let path = dir.join(uri);
+            std::fs::read(&path).map_err(|err| format!("{}: {}", path.display(), err))?
+        }
+    };
+    if data.len() < buffer.byte_length {
+        return Err(format!(
+            "buffer has {} bytes instead of {}",
+            data.len(),
+            buffer.byte_length
+        ));
+    }
+    Ok(data)
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in text.bytes().take_while(|c| *c != b'=') {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(format!("invalid base64 character '{}'", c as char)),
+        };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Convert the color from the linear color space to sRGB
+fn to_srgb(linear: [f32; 3]) -> Color {
+    let [r, g, b] = linear.map(|c| {
+        let c = c.clamp(0.0, 1.0);
+        let c = if c <= 0.003_130_8 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (c * u8::MAX as f32).round() as u8
+    });
+    Color::new(r, g, b)
+}
+
+struct Reader<'a> {
+    doc: &'a Document,
+    buffers: &'a [Vec<u8>],
+}
+
+impl Reader<'_> {
+    /// Return triangles of the primitive in the coordinates of its mesh
+    fn primitive(&self, p: &Primitive) -> Result<GltfMesh, String> {
+        let material = match p.material {
+            None => Material::default(),
+            Some(i) => self
+                .doc
+                .materials
+                .get(i)
+                .map(|m| m.pbr_metallic_roughness.clone())
+                .ok_or_else(|| format!("material {} doesn't exist", i))?,
+        };
+        let mut data = MeshData::default();
+
+        let position = *p
+            .attributes
+            .get("POSITION")
+            .ok_or_else(|| "primitive has no POSITION attribute".to_string())?;
+        data.vertices = self.vectors(position)?;
+        if let Some(normal) = p.attributes.get("NORMAL") {
+            data.normals = self.vectors(*normal)?;
+        }
+        if let Some(color) = p.attributes.get("COLOR_0") {
+            let (components, values) = self.accessor(*color)?;
+            if components < 3 {
+                return Err("COLOR_0 must have 3 or 4 components".to_string());
+            }
+            let base = material.base_color_factor;
+            data.colors = values
+                .chunks_exact(components)
+                .map(|c| to_srgb([0, 1, 2].map(|i| c[i] as f32 * base[i])))
+                .collect();
+        }
+        // normals and colors of the wrong length are dropped by the mesh
+        let len = data.vertices.len();
+
+        let indices: Vec<usize> = match p.indices {
+            None => (0..len).collect(),
+            Some(i) => self.accessor(i)?.1.iter().map(|i| *i as usize).collect(),
+        };
+        if let Some(i) = indices.iter().find(|i| **i >= len) {
+            return Err(format!("vertex index {} is out of range", i));
+        }
+        data.faces = match p.mode {
+            TRIANGLES => indices
+                .chunks_exact(3)
+                .map(|f| [f[0], f[1], f[2]])
+                .collect(),
+            TRIANGLE_STRIP => (2..indices.len())
+                .map(|k| {
+                    let (a, b) = (indices[k - 2], indices[k - 1]);
+                    // every second triangle of the strip keeps the winding
+                    if k % 2 == 0 {
+                        [a, b, indices[k]]
+                    } else {
+                        [b, a, indices[k]]
+                    }
+                })
+                .collect(),
+            TRIANGLE_FAN => (2..indices.len())
+                .map(|k| [indices[0], indices[k - 1], indices[k]])
+                .collect(),
+            // points and lines have no surface
+            _ => Vec::new(),
+        };
+        Ok(GltfMesh { data, material })
+    }
+
+    fn vectors(&self, accessor: usize) -> Result<Vec<Vector>, String> {
+        let (components, values) = self.accessor(accessor)?;
+        if components != 3 {
+            return Err(format!("accessor {} must be VEC3", accessor));
+        }
+        Ok(values
+            .chunks_exact(3)
+            .map(|v| Vector::new(v[0] as f32, v[1] as f32, v[2] as f32))
+            .collect())
+    }
+
+    /// Return number of the components of the elements and their values, integer values are
+    /// exact and normalized ones are turned into fractions
+    fn accessor(&self, index: usize) -> Result<(usize, Vec<f64>), String> {
+        let a = self
+            .doc
+            .accessors
+            .get(index)
+            .ok_or_else(|| format!("accessor {} doesn't exist", index))?;
+        if a.sparse.is_some() {
+            return Err(format!("sparse accessor {} isn't supported", index));
+        }
+        let components = match a.kind.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" | "MAT2" => 4,
+            "MAT3" => 9,
+            "MAT4" => 16,
+            _ => return Err(format!("unknown accessor type '{}'", a.kind)),
+        };
+        let size = match a.component_type {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT => 2,
+            UNSIGNED_INT | FLOAT => 4,
+            _ => return Err(format!("unknown component type {}", a.component_type)),
+        };
+        let too_big = || format!("accessor {} is out of buffer", index);
+        let len = a.count.checked_mul(components).ok_or_else(too_big)?;
+        // accessor without buffer view is filled by zeros, it isn't bigger than the buffers
+        let view = match a.buffer_view {
+            None => {
+                let buffers_len: usize = self.buffers.iter().map(|b| b.len()).sum();
+                if len
+                    .checked_mul(size)
+                    .is_none_or(|bytes| bytes > buffers_len)
+                {
+                    return Err(too_big());
+                }
+                return Ok((components, vec![0.0; len]));
+            }
+            Some(v) => self
+                .doc
+                .buffer_views
+                .get(v)
+                .ok_or_else(|| format!("buffer view {} doesn't exist", v))?,
+        };
+        let data = self
+            .buffers
+            .get(view.buffer)
+            .and_then(|b| {
+                let end = view.byte_offset.checked_add(view.byte_length)?;
+                b.get(view.byte_offset..end)
+            })
+            .ok_or_else(|| format!("buffer view of accessor {} is out of buffer", index))?;
+        let stride = view.byte_stride.unwrap_or(components * size);
+        // elements don't overlap, so the values aren't more than the bytes of the view
+        if stride < components * size {
+            return Err(format!(
+                "stride of accessor {} is less than its element",
+                index
+            ));
+        }
+        if a.count > 0 {
+            let end = (a.count - 1)
+                .checked_mul(stride)
+                .and_then(|e| e.checked_add(a.byte_offset))
+                .and_then(|e| e.checked_add(components * size));
+            if end.is_none_or(|end| end > data.len()) {
+                return Err(format!("accessor {} is out of buffer view", index));
+            }
+        }
+
+        let mut values = Vec::with_capacity(len);
+        for i in 0..a.count {
+            for c in 0..components {
+                let pos = a.byte_offset + i * stride + c * size;
+                let b = &data[pos..pos + size];
+                let v = match a.component_type {
+                    BYTE => b[0] as i8 as f64,
+                    UNSIGNED_BYTE => b[0] as f64,
+                    SHORT => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    UNSIGNED_SHORT => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    UNSIGNED_INT => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                };
+                values.push(if a.normalized {
+                    let max = match a.component_type {
+                        BYTE => i8::MAX as f64,
+                        UNSIGNED_BYTE => u8::MAX as f64,
+                        SHORT => i16::MAX as f64,
+                        _ => u16::MAX as f64,
+                    };
+                    (v / max).max(-1.0)
+                } else {
+                    v
+                });
+            }
+        }
+        Ok((components, values))
+    }
+}
+
+const BYTE: u32 = 5120;
+const UNSIGNED_BYTE: u32 = 5121;
+const SHORT: u32 = 5122;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+const TRIANGLES: u32 = 4;
+const TRIANGLE_STRIP: u32 = 5;
+const TRIANGLE_FAN: u32 = 6;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    #[serde(default)]
+    accessors: Vec<Accessor>,
+    #[serde(default)]
+    buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    buffers: Vec<Buffer>,
+    #[serde(default)]
+    cameras: Vec<Camera>,
+    #[serde(default)]
+    materials: Vec<MaterialEntry>,
+    #[serde(default)]
+    meshes: Vec<Mesh>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    scenes: Vec<Scene>,
+    scene: Option<usize>,
+    #[serde(default)]
+    extensions: Extensions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<serde::de::IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Deserialize)]
+struct Camera {
+    perspective: Option<Perspective>,
+}
+
+/// Aspect ratio of the camera is ignored, the image has its own
+#[derive(Deserialize)]
+struct Perspective {
+    yfov: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MaterialEntry {
+    #[serde(default)]
+    pbr_metallic_roughness: Material,
+}
+
+#[derive(Deserialize)]
+struct Mesh {
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Deserialize)]
+struct Primitive {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    #[serde(default = "default_mode")]
+    mode: u32,
+}
+
+fn default_mode() -> u32 {
+    TRIANGLES
+}
+
+#[derive(Deserialize)]
+struct Node {
+    #[serde(default)]
+    children: Vec<usize>,
+    camera: Option<usize>,
+    mesh: Option<usize>,
+    /// column-major matrix, it's used instead of translation, rotation and scale
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    /// unit quaternion [x, y, z, w]
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+    #[serde(default)]
+    extensions: NodeExtensions,
+}
+
+impl Node {
+    fn local_matrix(&self) -> Matrix {
+        if let Some(m) = &self.matrix {
+            return Matrix::from_columns(m);
+        }
+        let v = Vector::new_from_arr;
+        let t = self
+            .translation
+            .map_or_else(Matrix::identity, |t| Matrix::translation(&v(&t)));
+        let r = self
+            .rotation
+            .map_or_else(Matrix::identity, |r| Matrix::rotation_quaternion(&r));
+        let s = self
+            .scale
+            .map_or_else(Matrix::identity, |s| Matrix::scale(&v(&s)));
+        &(&t * &r) * &s
+    }
+}
+
+#[derive(Deserialize)]
+struct Scene {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[derive(Deserialize, Default)]
+struct Extensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    lights: Option<PunctualLights>,
+}
+
+#[derive(Deserialize)]
+struct PunctualLights {
+    lights: Vec<PunctualLight>,
+}
+
+#[derive(Deserialize)]
+struct PunctualLight {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize, Default)]
+struct NodeExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    lights: Option<NodeLight>,
+}
+
+#[derive(Deserialize)]
+struct NodeLight {
+    light: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::raytracer::matrix::Matrix;
+    use crate::raytracer::scene::loader::gltf::parse;
+    use crate::raytracer::vector::Vector;
+
+    #[test]
+    fn node_hierarchy_moves_embedded_triangle() {
+        // positions (0,0,0), (1,0,0), (0,1,0) as little-endian floats
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "translation": [0, 0, -5], "children": [1, 2] },
+                { "mesh": 0, "scale": [2, 2, 2] },
+                { "camera": 0, "rotation": [0, 0.7071068, 0, 0.7071068] }
+            ],
+            "cameras": [{ "type": "perspective", "perspective": { "yfov": 0.8, "znear": 0.1 } }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+            "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1] } }],
+            "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "buffers": [{
+                "byteLength": 36,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+            }]
+        }"#;
+        let scene = parse(json.as_bytes(), Path::new("."), &Matrix::identity()).unwrap();
+
+        assert_eq!(scene.meshes.len(), 1);
+        let mesh = &scene.meshes[0];
+        assert_eq!(mesh.data.faces, vec![[0, 1, 2]]);
+        assert_eq!(scene.instances.len(), 1);
+        let (i, m) = &scene.instances[0];
+        assert_eq!(*i, 0);
+        let expected = [[0.0, 0.0, -5.0], [2.0, 0.0, -5.0], [0.0, 2.0, -5.0]];
+        for (v, e) in mesh.data.vertices.iter().zip(expected.iter()) {
+            assert!((&m.point(v) - &Vector::new_from_arr(e)).size() < 1e-6);
+        }
+        assert_eq!(mesh.material.color().r, 255);
+
+        // camera turned by 90 degrees around Y looks along -X
+        let camera = scene.camera.unwrap();
+        assert!((&camera.view - &Vector::new(-1.0, 0.0, 0.0)).size() < 1e-5);
+        assert!((&camera.origin - &Vector::new(0.0, 0.0, -5.0)).size() < 1e-6);
+    }
+
+    #[test]
+    fn oversized_accessor_is_error() {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [{ "mesh": 0 }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+            "accessors": [ACCESSOR],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "buffers": [{
+                "byteLength": 36,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+            }]
+        }"#;
+        for accessor in [
+            // the end of the last element overflows
+            r#"{ "bufferView": 0, "componentType": 5126, "count": 4611686018427387904, "type": "VEC3" }"#,
+            // zeros without buffer view
+            r#"{ "componentType": 5126, "count": 1000000000000, "type": "VEC3" }"#,
+        ] {
+            let json = json.replace("ACCESSOR", accessor);
+            assert!(parse(json.as_bytes(), Path::new("."), &Matrix::identity()).is_err());
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Wavefront OBJ format, only geometry of the polygons and normals of the vertices are read:
+//! ```text
+//! # comment
+//! v 0.0 0.0 0.0
+//! v 1.0 0.0 0.0
+//! v 0.0 1.0 0.0
+//! f 1 2 3
+//! ```
+//! Faces with more than three vertices are split to triangles as a fan, so they are
+//! expected to be convex.
+//!
+//! OBJ indexes positions and normals separately, so the vertex with different normals in
+//! different faces becomes several vertices of the mesh.
+
+use std::collections::HashMap;
+
+use crate::raytracer::scene::shape::MeshData;
+use crate::raytracer::vector::Vector;
+
+/// Parse text of OBJ file, error is the line number and the message
+pub fn parse(text: &str) -> Result<MeshData, (usize, String)> {
+    let mut data = MeshData::default();
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    // normal of every vertex of the mesh, it's None if the face doesn't give it
+    let mut vertex_normals: Vec<Option<usize>> = Vec::new();
+    // vertices of the mesh by the position and the normal
+    let mut corners: HashMap<(usize, Option<usize>), usize> = HashMap::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_num = i + 1;
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut tokens = line.split_whitespace();
+        let res = match tokens.next() {
+            Some("v") => parse_vertex(tokens).map(|v| positions.push(v)),
+            Some("vn") => parse_vertex(tokens).map(|n| normals.push(n)),
+            Some("f") => parse_face(tokens, positions.len(), normals.len()).map(|polygon| {
+                let polygon: Vec<usize> = polygon
+                    .into_iter()
+                    .map(|corner| {
+                        *corners.entry(corner).or_insert_with(|| {
+                            data.vertices.push(positions[corner.0].clone());
+                            vertex_normals.push(corner.1);
+                            data.vertices.len() - 1
+                        })
+                    })
+                    .collect();
+                for k in 1..polygon.len() - 1 {
+                    data.faces.push([polygon[0], polygon[k], polygon[k + 1]]);
+                }
+                data.polygons.push(polygon);
+            }),
+            // texture coordinates, groups, materials and other are ignored
+            _ => Ok(()),
+        };
+        res.map_err(|message| (line_num, message))?;
+    }
+
+    // normals are used only if all the vertices have them
+    if let Some(vertex_normals) = vertex_normals.into_iter().collect::<Option<Vec<usize>>>() {
+        data.normals = vertex_normals
+            .into_iter()
+            .map(|n| normals[n].clone())
+            .collect();
+    }
+    Ok(data)
+}
+
+fn parse_vertex<'a, I: Iterator<Item = &'a str>>(tokens: I) -> Result<Vector, String> {
+    let coords = tokens
+        .map(|t| {
+            t.parse::<f32>()
+                .map_err(|_| format!("invalid vertex coordinate '{}'", t))
+        })
+        .collect::<Result<Vec<f32>, String>>()?;
+    if coords.len() < 3 {
+        return Err("vertex must have 3 coordinates".to_string());
+    }
+    Ok(Vector::new(coords[0], coords[1], coords[2]))
+}
+
+/// Return indices of the positions and the normals of the polygon vertices, positions_len
+/// and normals_len are numbers of positions and normals read so far
+fn parse_face<'a, I: Iterator<Item = &'a str>>(
+    tokens: I,
+    positions_len: usize,
+    normals_len: usize,
+) -> Result<Vec<(usize, Option<usize>)>, String> {
+    let polygon = tokens
+        .map(|t| {
+            let mut refs = t.split('/');
+            let v = parse_index(refs.next().unwrap_or(t), positions_len)?;
+            // references to normals are ignored if the file has no normals
+            let n = match refs.nth(1) {
+                Some(n) if !n.is_empty() && normals_len > 0 => Some(parse_index(n, normals_len)?),
+                _ => None,
+            };
+            Ok((v, n))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if polygon.len() < 3 {
+        return Err("face must have at least 3 vertices".to_string());
+    }
+    Ok(polygon)
+}
+
+/// Parse index of the vertex reference of the face (v, v/vt, v/vt/vn or v//vn), indices
+/// start from 1 and negative indices count from the last read element
+fn parse_index(token: &str, len: usize) -> Result<usize, String> {
+    let index = token
+        .parse::<i64>()
+        .map_err(|_| format!("invalid vertex index '{}'", token))?;
+    let index = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+    if index < 0 || index >= len as i64 {
+        return Err(format!("vertex index '{}' is out of range", token));
+    }
+    Ok(index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::scene::loader::obj::parse;
+
+    #[test]
+    fn quad_is_triangulated() {
+        let text = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1/1/1 2/2/1 3/3/1 -1//1\n";
+        let data = parse(text).unwrap();
+        assert_eq!(data.faces, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(data.polygons, vec![vec![0, 1, 2, 3]]);
+    }
+
+    #[test]
+    fn error_has_line_number() {
+        let text = "v 0 0 0\nv 1 0 0\n\nf 1 2 3\n";
+        let (line, _) = parse(text).err().unwrap();
+        assert_eq!(line, 4);
+    }
+
+    #[test]
+    fn vertex_with_two_normals_is_split() {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nvn 0 0 1\nvn 0 1 0\n\
+                    f 1//1 2//1 3//1\nf 1//2 4//2 2//2\n";
+        let data = parse(text).unwrap();
+        assert_eq!(data.vertices.len(), 6);
+        assert_eq!(data.normals.len(), 6);
+        assert_eq!(data.faces, vec![[0, 1, 2], [3, 4, 5]]);
+    }
+}
@@ -0,0 +1,94 @@
+//! Bézier patches in the format of the Utah teapot data: the number of patches, the lines
+//! of 16 indices (starting at 1) of the control points of every patch, the number of the
+//! control points and the lines of their coordinates. Numbers are separated by commas or
+//! whitespaces.
+
+use crate::raytracer::scene::shape::Patch;
+use crate::raytracer::vector::Vector;
+
+/// Parse content of the patch file, error is the line number and the message
+pub fn parse(text: &str) -> Result<Vec<Patch>, (usize, String)> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty());
+    let mut next = |what: &str| {
+        lines
+            .next()
+            .ok_or_else(|| (text.lines().count(), format!("expected {}", what)))
+    };
+
+    let (line, l) = next("number of patches")?;
+    let patch_count: usize = parse_numbers(l, line, 1)?[0];
+    let mut indices = Vec::with_capacity(patch_count);
+    for _ in 0..patch_count {
+        let (line, l) = next("indices of the patch")?;
+        indices.push((line, parse_numbers::<usize>(l, line, 16)?));
+    }
+
+    let (line, l) = next("number of points")?;
+    let point_count: usize = parse_numbers(l, line, 1)?[0];
+    let mut points = Vec::with_capacity(point_count);
+    for _ in 0..point_count {
+        let (line, l) = next("point")?;
+        let c = parse_numbers::<f32>(l, line, 3)?;
+        points.push(Vector::new(c[0], c[1], c[2]));
+    }
+
+    indices
+        .into_iter()
+        .map(|(line, idx)| {
+            let mut patch = Vec::with_capacity(16);
+            for i in idx {
+                match i.checked_sub(1).and_then(|i| points.get(i)) {
+                    Some(p) => patch.push(p.clone()),
+                    None => return Err((line, format!("point index {} out of range", i))),
+                }
+            }
+            Ok(Patch {
+                points: patch.try_into().unwrap(),
+            })
+        })
+        .collect()
+}
+
+fn parse_numbers<T: std::str::FromStr>(
+    l: &str,
+    line: usize,
+    count: usize,
+) -> Result<Vec<T>, (usize, String)> {
+    let numbers = l
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse()
+                .map_err(|_| (line, format!("invalid number \"{}\"", s)))
+        })
+        .collect::<Result<Vec<T>, _>>()?;
+    if numbers.len() != count {
+        return Err((
+            line,
+            format!("expected {} numbers, found {}", count, numbers.len()),
+        ));
+    }
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::scene::loader::patch::parse;
+    use crate::raytracer::vector::Vector;
+
+    #[test]
+    fn indices_start_at_one() {
+        let points: String = (0..16).map(|i| format!("{}, 0.0, 0.0\n", i)).collect();
+        let text = format!("1\n16,1,2,3,4,5,6,7,8,9,10,11,12,13,14,15\n16\n{}", points);
+        let patches = parse(&text).unwrap();
+        assert_eq!(patches[0].points[0], Vector::new(15.0, 0.0, 0.0));
+        assert_eq!(patches[0].points[1], Vector::new(0.0, 0.0, 0.0));
+
+        let text = text.replacen("16,", "17,", 1);
+        assert_eq!(parse(&text).err().unwrap().0, 2);
+    }
+}
@@ -0,0 +1,393 @@
+//! Polygon File Format (PLY) in ASCII and binary little-endian encodings:
+//! ```text
+//! ply
+//! format ascii 1.0
+//! element vertex 3
+//! property float x
+//! property float y
+//! property float z
+//! property uchar red
+//! property uchar green
+//! property uchar blue
+//! element face 1
+//! property list uchar int vertex_indices
+//! end_header
+//! 0 0 0 255 0 0
+//! 1 0 0 0 255 0
+//! 0 1 0 0 0 255
+//! 3 0 1 2
+//! ```
+//! Vertex normals (nx, ny, nz) and colors (red, green, blue) are optional, other elements
+//! and properties are skipped.
+
+use crate::image::Color;
+use crate::raytracer::scene::shape::MeshData;
+use crate::raytracer::vector::Vector;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Scalar, String> {
+        match name {
+            "char" | "int8" => Ok(Scalar::I8),
+            "uchar" | "uint8" => Ok(Scalar::U8),
+            "short" | "int16" => Ok(Scalar::I16),
+            "ushort" | "uint16" => Ok(Scalar::U16),
+            "int" | "int32" => Ok(Scalar::I32),
+            "uint" | "uint32" => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double" | "float64" => Ok(Scalar::F64),
+            _ => Err(format!("unknown property type '{}'", name)),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    /// name, type of the list length and type of the items
+    List(String, Scalar, Scalar),
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar(name, _) | Property::List(name, _, _) => name,
+        }
+    }
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reader of the values of the element rows in the body of the file
+trait Body {
+    /// Start reading of the next row
+    fn next_row(&mut self) -> Result<(), String>;
+    fn read(&mut self, ty: Scalar) -> Result<f64, String>;
+    /// Line of the file where the current row is, if the file is text
+    fn line(&self) -> Option<usize>;
+}
+
+struct AsciiBody<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    /// number of the lines before the body
+    offset: usize,
+    line: usize,
+    tokens: std::str::SplitWhitespace<'a>,
+}
+
+impl<'a> Body for AsciiBody<'a> {
+    fn next_row(&mut self) -> Result<(), String> {
+        for (i, line) in self.lines.by_ref() {
+            if !line.trim().is_empty() {
+                self.line = self.offset + i + 1;
+                self.tokens = line.split_whitespace();
+                return Ok(());
+            }
+        }
+        Err("unexpected end of file".to_string())
+    }
+
+    fn read(&mut self, ty: Scalar) -> Result<f64, String> {
+        let token = self
+            .tokens
+            .next()
+            .ok_or_else(|| "not enough values in the row".to_string())?;
+        let value = token
+            .parse::<f64>()
+            .map_err(|_| format!("invalid value '{}'", token))?;
+        if ty != Scalar::F32 && ty != Scalar::F64 && value.fract() != 0.0 {
+            return Err(format!("invalid integer value '{}'", token));
+        }
+        Ok(value)
+    }
+
+    fn line(&self) -> Option<usize> {
+        Some(self.line)
+    }
+}
+
+struct BinaryBody<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Body for BinaryBody<'a> {
+    fn next_row(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn read(&mut self, ty: Scalar) -> Result<f64, String> {
+        if self.bytes.len() < ty.size() {
+            return Err("unexpected end of file".to_string());
+        }
+        let (value, rest) = self.bytes.split_at(ty.size());
+        self.bytes = rest;
+        let value = match ty {
+            Scalar::I8 => value[0] as i8 as f64,
+            Scalar::U8 => value[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([value[0], value[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([value[0], value[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([value[0], value[1], value[2], value[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([value[0], value[1], value[2], value[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([value[0], value[1], value[2], value[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes([
+                value[0], value[1], value[2], value[3], value[4], value[5], value[6], value[7],
+            ]),
+        };
+        Ok(value)
+    }
+
+    fn line(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Parse content of PLY file, error is the line number (for text parts) and the message
+pub fn parse(bytes: &[u8]) -> Result<MeshData, (Option<usize>, String)> {
+    const END_HEADER: &[u8] = b"end_header";
+
+    let header_end = bytes
+        .windows(END_HEADER.len())
+        .position(|w| w == END_HEADER)
+        .ok_or((None, "header has no end_header".to_string()))?;
+    let body_start = match bytes[header_end..].iter().position(|&b| b == b'\n') {
+        Some(newline) => header_end + newline + 1,
+        None => bytes.len(),
+    };
+    let header = std::str::from_utf8(&bytes[..header_end])
+        .map_err(|_| (None, "header is not a text".to_string()))?;
+
+    let (format, elements) = parse_header(header).map_err(|(line, msg)| (Some(line), msg))?;
+    let header_lines = header.lines().count() + 1;
+
+    match format.as_str() {
+        "ascii" => {
+            let text = std::str::from_utf8(&bytes[body_start..])
+                .map_err(|_| (None, "body is not a text".to_string()))?;
+            let mut body = AsciiBody {
+                lines: text.lines().enumerate(),
+                offset: header_lines,
+                line: header_lines,
+                tokens: "".split_whitespace(),
+            };
+            read_body(&mut body, &elements)
+        }
+        "binary_little_endian" => {
+            let mut body = BinaryBody {
+                bytes: &bytes[body_start..],
+            };
+            read_body(&mut body, &elements)
+        }
+        _ => Err((None, format!("unsupported format '{}'", format))),
+    }
+}
+
+/// Return format and elements of the file, error is the line number and the message
+fn parse_header(header: &str) -> Result<(String, Vec<Element>), (usize, String)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for (i, line) in header.lines().enumerate() {
+        let line_num = i + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let err = |msg: &str| (line_num, format!("{} '{}'", msg, line));
+        match tokens.as_slice() {
+            ["ply"] if line_num == 1 => {}
+            _ if line_num == 1 => return Err(err("file must start with 'ply', found")),
+            ["format", f, _] => format = Some(f.to_string()),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| err("invalid element"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", len_ty, item_ty, name] => {
+                let prop = Property::List(
+                    name.to_string(),
+                    Scalar::parse(len_ty).map_err(|msg| (line_num, msg))?,
+                    Scalar::parse(item_ty).map_err(|msg| (line_num, msg))?,
+                );
+                match elements.last_mut() {
+                    Some(e) => e.properties.push(prop),
+                    None => return Err(err("property before element")),
+                }
+            }
+            ["property", ty, name] => {
+                let prop = Property::Scalar(
+                    name.to_string(),
+                    Scalar::parse(ty).map_err(|msg| (line_num, msg))?,
+                );
+                match elements.last_mut() {
+                    Some(e) => e.properties.push(prop),
+                    None => return Err(err("property before element")),
+                }
+            }
+            _ => return Err(err("invalid header line")),
+        }
+    }
+
+    match format {
+        Some(format) => Ok((format, elements)),
+        None => Err((1, "header has no format".to_string())),
+    }
+}
+
+fn read_body<B: Body>(
+    body: &mut B,
+    elements: &[Element],
+) -> Result<MeshData, (Option<usize>, String)> {
+    let mut data = MeshData::default();
+
+    for element in elements.iter() {
+        let find = |name: &str| element.properties.iter().position(|p| p.name() == name);
+        let color_scale = |name: &str| match element.properties.iter().find(|p| p.name() == name) {
+            Some(Property::Scalar(_, Scalar::F32 | Scalar::F64)) => u8::MAX as f64,
+            _ => 1.0,
+        };
+        let xyz = [find("x"), find("y"), find("z")];
+        let normal = [find("nx"), find("ny"), find("nz")];
+        let rgb = [find("red"), find("green"), find("blue")];
+        let indices = find("vertex_indices").or_else(|| find("vertex_index"));
+        let scale = color_scale("red");
+
+        for _ in 0..element.count {
+            let row = read_row(body, element).map_err(|msg| (body.line(), msg))?;
+            let err = |msg: String| (body.line(), msg);
+            match element.name.as_str() {
+                "vertex" => {
+                    let get = |i: Option<usize>| i.map(|i| row[i][0] as f32);
+                    match xyz.map(get) {
+                        [Some(x), Some(y), Some(z)] => data.vertices.push(Vector::new(x, y, z)),
+                        _ => return Err(err("vertex must have x, y and z".to_string())),
+                    }
+                    if let [Some(x), Some(y), Some(z)] = normal.map(get) {
+                        data.normals.push(Vector::new(x, y, z));
+                    }
+                    if let [Some(r), Some(g), Some(b)] = rgb {
+                        let c = |i: usize| (row[i][0] * scale).round().clamp(0.0, 255.0) as u8;
+                        data.colors.push(Color::new(c(r), c(g), c(b)));
+                    }
+                }
+                "face" => {
+                    let polygon = match indices {
+                        Some(i) => &row[i],
+                        None => return Err(err("face must have vertex_indices".to_string())),
+                    };
+                    if polygon.len() < 3 {
+                        return Err(err("face must have at least 3 vertices".to_string()));
+                    }
+                    // indices may be read as floats, casting would turn -1 into 0
+                    if polygon.iter().any(|v| *v < 0.0 || v.fract() != 0.0) {
+                        return Err(err("vertex index is out of range".to_string()));
+                    }
+                    for k in 1..polygon.len() - 1 {
+                        data.faces
+                            .push([polygon[0], polygon[k], polygon[k + 1]].map(|v| v as usize));
+                    }
+                    data.polygons
+                        .push(polygon.iter().map(|v| *v as usize).collect());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let vertices_len = data.vertices.len();
+    if data.faces.iter().flatten().any(|&v| v >= vertices_len) {
+        return Err((None, "vertex index is out of range".to_string()));
+    }
+    Ok(data)
+}
+
+/// Read values of all properties of the element, scalar property is a list of one value
+fn read_row<B: Body>(body: &mut B, element: &Element) -> Result<Vec<Vec<f64>>, String> {
+    body.next_row()?;
+    let mut row = Vec::with_capacity(element.properties.len());
+    for p in element.properties.iter() {
+        match p {
+            Property::Scalar(_, ty) => row.push(vec![body.read(*ty)?]),
+            Property::List(_, len_ty, item_ty) => {
+                let len = body.read(*len_ty)?;
+                if len < 0.0 {
+                    return Err("negative list length".to_string());
+                }
+                let list = (0..len as usize)
+                    .map(|_| body.read(*item_ty))
+                    .collect::<Result<Vec<f64>, String>>()?;
+                row.push(list);
+            }
+        }
+    }
+    Ok(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::scene::loader::ply::parse;
+
+    #[test]
+    fn ascii_and_binary_are_the_same() {
+        let header = "ply\nformat {}\nelement vertex 3\nproperty float x\nproperty float y\n\
+                      property float z\nproperty uchar red\nproperty uchar green\n\
+                      property uchar blue\nelement face 1\n\
+                      property list uchar int vertex_indices\nend_header\n";
+        let ascii = header.replace("{}", "ascii 1.0")
+            + "0 0 0 255 0 0\n1 0 0 0 255 0\n0 1 0 0 0 255\n3 0 1 2\n";
+
+        let mut binary = header
+            .replace("{}", "binary_little_endian 1.0")
+            .into_bytes();
+        for (xyz, rgb) in [
+            ([0.0f32, 0.0, 0.0], [255u8, 0, 0]),
+            ([1.0, 0.0, 0.0], [0, 255, 0]),
+            ([0.0, 1.0, 0.0], [0, 0, 255]),
+        ] {
+            xyz.iter().for_each(|c| binary.extend(c.to_le_bytes()));
+            binary.extend(rgb);
+        }
+        binary.push(3);
+        [0i32, 1, 2]
+            .iter()
+            .for_each(|v| binary.extend(v.to_le_bytes()));
+
+        let ascii = parse(ascii.as_bytes()).unwrap();
+        let binary = parse(&binary).unwrap();
+        assert_eq!(ascii.vertices, binary.vertices);
+        assert_eq!(ascii.faces, vec![[0, 1, 2]]);
+        assert_eq!(binary.faces, vec![[0, 1, 2]]);
+        assert_eq!(binary.colors[1].g, 255);
+    }
+
+    #[test]
+    fn negative_index_is_error() {
+        let text = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\n\
+                    property float y\nproperty float z\nelement face 1\n\
+                    property list uchar int vertex_indices\nend_header\n\
+                    0 0 0\n1 0 0\n0 1 0\n3 0 1 -1\n";
+        assert!(parse(text.as_bytes()).is_err());
+        assert!(parse(text.replace("-1", "1.5").as_bytes()).is_err());
+        assert!(parse(text.replace("-1", "2").as_bytes()).is_ok());
+    }
+}
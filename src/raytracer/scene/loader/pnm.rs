@@ -0,0 +1,106 @@
+//! Netpbm grayscale (PGM) and color (PPM) images in ASCII and binary encodings, they are
+//! read as height maps. Color pixels are turned into gray by the average of the channels.
+
+use crate::raytracer::scene::shape::HeightMap;
+
+/// Parse content of PGM or PPM file
+pub fn parse(bytes: &[u8]) -> Result<HeightMap, String> {
+    let mut header = Header { bytes, pos: 0 };
+    let magic = header.token()?;
+    let (channels, binary) = match magic.as_str() {
+        "P2" => (1, false),
+        "P5" => (1, true),
+        "P3" => (3, false),
+        "P6" => (3, true),
+        _ => return Err(format!("unsupported image format \"{}\"", magic)),
+    };
+    let columns = header.number()?;
+    let rows = header.number()?;
+    let max = header.number()?;
+    if columns < 2 || rows < 2 {
+        return Err("image must be at least 2x2 pixels".to_string());
+    }
+    if max == 0 || max > u16::MAX as usize {
+        return Err(format!("invalid max value {}", max));
+    }
+
+    let count = columns * rows * channels;
+    let values = if binary {
+        // single whitespace separates the header from the data
+        let data = &bytes[usize::min(header.pos + 1, bytes.len())..];
+        let size = if max > u8::MAX as usize { 2 } else { 1 };
+        if data.len() < count * size {
+            return Err("unexpected end of image data".to_string());
+        }
+        data.chunks_exact(size)
+            .take(count)
+            .map(|b| b.iter().fold(0, |v, &b| v * 256 + b as usize))
+            .collect()
+    } else {
+        (0..count)
+            .map(|_| header.number())
+            .collect::<Result<Vec<usize>, String>>()?
+    };
+
+    let samples = values
+        .chunks_exact(channels)
+        .map(|p| p.iter().sum::<usize>() as f32 / (channels * max) as f32)
+        .collect();
+    Ok(HeightMap {
+        columns,
+        rows,
+        samples,
+    })
+}
+
+/// Reader of whitespace separated tokens with "#" comments
+struct Header<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Header<'_> {
+    fn token(&mut self) -> Result<String, String> {
+        loop {
+            match self.bytes.get(self.pos) {
+                None => return Err("unexpected end of image".to_string()),
+                Some(b'#') => {
+                    while !matches!(self.bytes.get(self.pos), None | Some(b'\n')) {
+                        self.pos += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+            }
+        }
+        let start = self.pos;
+        while matches!(self.bytes.get(self.pos), Some(b) if !b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned())
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let token = self.token()?;
+        token
+            .parse()
+            .map_err(|_| format!("invalid number \"{}\"", token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::scene::loader::pnm::parse;
+
+    #[test]
+    fn ascii_and_binary_are_the_same() {
+        let ascii = b"P2\n# comment\n2 2\n255\n0 51\n102 255\n";
+        let mut binary = b"P5 2 2 255\n".to_vec();
+        binary.extend_from_slice(&[0, 51, 102, 255]);
+
+        let a = parse(ascii).unwrap();
+        let b = parse(&binary).unwrap();
+        assert_eq!(a.samples, b.samples);
+        assert_eq!(a.samples, vec![0.0, 0.2, 0.4, 1.0]);
+    }
+}
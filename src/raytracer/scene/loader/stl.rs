@@ -0,0 +1,179 @@
+//! Stereolithography (STL) format in ASCII and binary encodings.
+//!
+//! STL stores every facet with its own copy of the vertices, so the same vertices are merged
+//! to make the mesh connected.
+
+use std::collections::HashMap;
+
+use crate::raytracer::scene::shape::MeshData;
+use crate::raytracer::vector::Vector;
+
+/// Size of binary STL header and the number of facets after it
+const BINARY_HEADER: usize = 84;
+/// Size of binary STL facet: normal, 3 vertices and attribute
+const BINARY_FACET: usize = 50;
+
+/// Parse content of STL file, error is the line number (for ASCII file) and the message
+pub fn parse(bytes: &[u8]) -> Result<MeshData, (Option<usize>, String)> {
+    if is_binary(bytes) {
+        return parse_binary(bytes).map_err(|msg| (None, msg));
+    }
+    let text = std::str::from_utf8(bytes).map_err(|_| (None, "invalid STL file".to_string()))?;
+    parse_ascii(text).map_err(|(line, msg)| (Some(line), msg))
+}
+
+/// ASCII file starts with "solid", but binary file may start with it too, so the size of the
+/// file is checked first
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() < BINARY_HEADER {
+        return false;
+    }
+    bytes.len() == binary_size(bytes) || !bytes.starts_with(b"solid")
+}
+
+/// Return the size of the binary file by the number of facets in its header
+fn binary_size(bytes: &[u8]) -> usize {
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    BINARY_HEADER + count * BINARY_FACET
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<MeshData, String> {
+    let size = binary_size(bytes);
+    if bytes.len() != size {
+        return Err(format!(
+            "binary STL must have {} bytes for the facets in the header, but it has {}",
+            size,
+            bytes.len()
+        ));
+    }
+    let mut builder = Builder::new();
+    for facet in bytes[BINARY_HEADER..].chunks_exact(BINARY_FACET) {
+        let coord = |i: usize| {
+            let b = &facet[i * 4..i * 4 + 4];
+            f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        };
+        // the first 3 floats are the facet normal, it's computed from the vertices anyway
+        let polygon = [3, 6, 9].map(|i| Vector::new(coord(i), coord(i + 1), coord(i + 2)));
+        builder.push_polygon(&polygon);
+    }
+    Ok(builder.data)
+}
+
+/// Parse ASCII STL, error is the line number and the message:
+/// ```text
+/// solid name
+///   facet normal 0 0 1
+///     outer loop
+///       vertex 0 0 0
+///       vertex 1 0 0
+///       vertex 0 1 0
+///     endloop
+///   endfacet
+/// endsolid name
+/// ```
+fn parse_ascii(text: &str) -> Result<MeshData, (usize, String)> {
+    let mut builder = Builder::new();
+    let mut polygon = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_num = i + 1;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("vertex") => {
+                let coords = tokens
+                    .map(|t| t.parse::<f32>())
+                    .collect::<Result<Vec<f32>, _>>()
+                    .map_err(|_| (line_num, format!("invalid vertex '{}'", line.trim())))?;
+                if coords.len() != 3 {
+                    return Err((line_num, "vertex must have 3 coordinates".to_string()));
+                }
+                polygon.push(Vector::new(coords[0], coords[1], coords[2]));
+            }
+            Some("endloop") => {
+                if polygon.len() < 3 {
+                    return Err((line_num, "facet must have at least 3 vertices".to_string()));
+                }
+                builder.push_polygon(&polygon);
+                polygon.clear();
+            }
+            Some("solid" | "endsolid" | "facet" | "outer" | "endfacet") | None => {}
+            Some(keyword) => return Err((line_num, format!("unknown keyword '{}'", keyword))),
+        }
+    }
+
+    Ok(builder.data)
+}
+
+/// Mesh builder merging the vertices with the same coordinates
+struct Builder {
+    data: MeshData,
+    indices: HashMap<[u32; 3], usize>,
+}
+
+impl Builder {
+    fn new() -> Builder {
+        Builder {
+            data: MeshData::default(),
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Push the polygon as the fan of triangles, triangles collapsed by the merging of the
+    /// vertices are skipped
+    fn push_polygon(&mut self, polygon: &[Vector]) {
+        let polygon: Vec<usize> = polygon.iter().map(|v| self.vertex_index(v)).collect();
+        for k in 1..polygon.len() - 1 {
+            let (a, b, c) = (polygon[0], polygon[k], polygon[k + 1]);
+            if a != b && b != c && c != a {
+                self.data.faces.push([a, b, c]);
+            }
+        }
+    }
+
+    fn vertex_index(&mut self, v: &Vector) -> usize {
+        // -0.0 and 0.0 are the same vertex
+        let key = v.to_arr().map(|c| (c + 0.0).to_bits());
+        let vertices = &mut self.data.vertices;
+        *self.indices.entry(key).or_insert_with(|| {
+            vertices.push(v.clone());
+            vertices.len() - 1
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::scene::loader::stl::{parse, BINARY_FACET, BINARY_HEADER};
+
+    #[test]
+    fn vertices_are_merged() {
+        let text = "solid quad\n\
+                    facet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 1 1 0\n\
+                    endloop\nendfacet\n\
+                    facet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 1 0\nvertex 0 1 0\n\
+                    endloop\nendfacet\n\
+                    endsolid quad\n";
+        let data = parse(text.as_bytes()).unwrap();
+        assert_eq!(data.vertices.len(), 4);
+        assert_eq!(data.faces, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn collapsed_facets_are_skipped() {
+        let text = "solid line\n\
+                    facet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 1 0 0\n\
+                    endloop\nendfacet\n\
+                    endsolid line\n";
+        let data = parse(text.as_bytes()).unwrap();
+        assert!(data.faces.is_empty());
+    }
+
+    #[test]
+    fn truncated_binary_file_is_error() {
+        let mut bytes = vec![0; BINARY_HEADER + 2 * BINARY_FACET];
+        bytes[80] = 2;
+        assert!(parse(&bytes).is_ok());
+        bytes.truncate(bytes.len() - 10);
+        assert!(parse(&bytes).is_err());
+    }
+}
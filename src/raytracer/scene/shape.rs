@@ -1,19 +1,191 @@
+use std::sync::Arc;
+
+use crate::image::Color;
+use crate::raytracer::packet::{lanes, RayPacket, LANES};
 use crate::raytracer::ray::Ray;
 use crate::raytracer::vector::Vector;
 
-pub trait Shape {
+mod bezier;
+mod boxes;
+mod bvh;
+mod cone;
+mod csg;
+mod curves;
+mod disk;
+mod heightfield;
+mod mesh;
+mod metaballs;
+mod plane;
+mod poly;
+mod quad;
+mod quadric;
+mod sdf;
+mod torus;
+mod transform;
+mod triangle;
+
+pub use bezier::{tessellate, Patch};
+pub use boxes::{new_aabox, new_oriented_box};
+pub use bvh::{Aabb, Bvh};
+pub use cone::{new_cone, new_cylinder};
+pub use csg::{new_csg, Operation};
+pub use curves::{new_curves, Curve, CurveKind};
+pub use disk::new_disk;
+pub use heightfield::{new_heightfield, HeightMap};
+pub use mesh::{Mesh, MeshData, Scheme};
+pub use metaballs::{new_metaballs, Ball};
+pub use plane::new_plane;
+pub use quad::new_quad;
+pub use quadric::{new_ellipsoid, new_hyperboloid, new_paraboloid, new_quadric};
+pub use sdf::{new_sdf, Field};
+pub use torus::new_torus;
+pub use transform::new_transformed;
+pub use triangle::new_triangle;
+
+pub trait Shape: Send + Sync {
     fn intersec(&self, ray: &Ray) -> Intersec;
     fn norm(&self, point: &Vector) -> Vector;
+
+    /// Return surface of the shape where the ray hits it at the distance.
+    ///
+    /// By default normal is taken from the hit point only, shapes which need more than
+    /// the point to find normal (for example which face of the mesh was hit) override it.
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        let point = ray.point_on_ray(distance);
+        Hit {
+            norm: self.norm(&point),
+            shading_norm: None,
+            color: None,
+            uv: None,
+            point,
+        }
+    }
+
+    /// Return the box around the shape, None if the shape is unbounded (for example plane),
+    /// then it's tested by every ray
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
+
+    /// Return distances to the closest hits in front of the rays of the packet, the same as
+    /// get_closer of intersec of every ray. By default the rays are tested one by one,
+    /// shapes computing all the lanes at once override it.
+    fn intersec_packet(&self, packet: &RayPacket) -> [Option<f32>; LANES] {
+        std::array::from_fn(|l| {
+            if l < packet.count() {
+                self.intersec(packet.ray(l)).get_closer()
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Shape shared by several objects (instances of the same geometry)
+impl<S: Shape + ?Sized> Shape for Arc<S> {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        (**self).intersec(ray)
+    }
+
+    fn norm(&self, point: &Vector) -> Vector {
+        (**self).norm(point)
+    }
+
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        (**self).hit(ray, distance)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        (**self).bounds()
+    }
+
+    fn intersec_packet(&self, packet: &RayPacket) -> [Option<f32>; LANES] {
+        (**self).intersec_packet(packet)
+    }
 }
 
+/// Surface of the shape at the point hit by a ray
+pub struct Hit {
+    pub point: Vector,
+    /// Normal of the real surface
+    pub norm: Vector,
+    /// Normal used for shading if it differs from the geometric one, for example normal
+    /// interpolated over the face of the mesh
+    pub shading_norm: Option<Vector>,
+    /// Color of the surface if the shape has its own colors instead of the object color
+    pub color: Option<Color>,
+    /// Texture coordinates of the point if the shape has them, they aren't used by shading
+    /// until textures are supported
+    #[allow(dead_code)]
+    pub uv: Option<(f32, f32)>,
+}
+
+/// Relative error of the distance given to the hit, the distance may come through the
+/// transform of the ray
+const HIT_TOLERANCE: f32 = 1e-4;
+
+/// Turn the normal to the side from which the ray comes, so flat shapes are lit from both
+/// sides.
+fn face_forward(n: Vector, ray: &Ray) -> Vector {
+    if n.dot(ray.get_dir()) > 0.0 {
+        -1.0 * &n
+    } else {
+        n
+    }
+}
+
+/// Distances from the ray origin to the points where the ray hits the shape in ascending
+/// order, the ray enters the shape at odd hits and leaves it at even ones.
+///
+/// Solid shapes report hits behind the ray origin too, so CSG can combine them.
 #[derive(Debug)]
 pub enum Intersec {
     One(f32),
     Two(f32, f32),
+    Four(f32, f32, f32, f32),
+    /// Intervals (enter, leave) of the ray inside the shape
+    Intervals(Vec<(f32, f32)>),
     None,
 }
 
 impl Intersec {
+    /// Return intervals (enter, leave) of the ray inside the shape, a single hit is the
+    /// interval of zero length
+    pub fn into_intervals(self) -> Vec<(f32, f32)> {
+        match self {
+            Self::None => Vec::new(),
+            Self::One(d) => vec![(d, d)],
+            Self::Two(d1, d2) => vec![(d1, d2)],
+            Self::Four(d1, d2, d3, d4) => vec![(d1, d2), (d3, d4)],
+            Self::Intervals(intervals) => intervals,
+        }
+    }
+
+    /// Make Intersec from the intervals (enter, leave) going in ascending order
+    pub fn from_intervals(intervals: Vec<(f32, f32)>) -> Intersec {
+        match intervals[..] {
+            [] => Self::None,
+            [(d1, d2)] => Self::Two(d1, d2),
+            _ => Self::Intervals(intervals),
+        }
+    }
+
+    /// Apply the function to every distance, it must keep their order
+    pub fn map<F: Fn(f32) -> f32>(self, f: F) -> Intersec {
+        match self {
+            Self::None => Self::None,
+            Self::One(d) => Self::One(f(d)),
+            Self::Two(d1, d2) => Self::Two(f(d1), f(d2)),
+            Self::Four(d1, d2, d3, d4) => Self::Four(f(d1), f(d2), f(d3), f(d4)),
+            Self::Intervals(intervals) => Self::Intervals(
+                intervals
+                    .into_iter()
+                    .map(|(d1, d2)| (f(d1), f(d2)))
+                    .collect(),
+            ),
+        }
+    }
+
     pub fn get_closer(self) -> Option<f32> {
         match self {
             Self::None => None,
@@ -25,6 +197,19 @@ impl Intersec {
                     None
                 }
             }
+            Self::Four(d1, d2, d3, d4) => {
+                if d1 > 0.0 && d2 > 0.0 {
+                    Some(d1)
+                } else if d3 > 0.0 && d4 > 0.0 {
+                    Some(d3)
+                } else {
+                    None
+                }
+            }
+            Self::Intervals(intervals) => intervals
+                .into_iter()
+                .find(|(d1, d2)| *d1 > 0.0 && *d2 > 0.0)
+                .map(|(d1, _)| d1),
         }
     }
 }
@@ -46,10 +231,6 @@ impl Shape for Sphere {
         let oc = &self.center - ray.get_orig();
         let oc_dir = oc.dot(ray.get_dir());
 
-        if oc_dir <= 0.0 {
-            return Intersec::None;
-        }
-
         let h2 = oc.cross(ray.get_dir()).dot2();
 
         let k = self.radius2 - h2;
@@ -57,7 +238,7 @@ impl Shape for Sphere {
             return Intersec::None;
         }
 
-        if k == 0.0 {
+        if k == 0.0 && oc_dir > 0.0 {
             return Intersec::One(oc_dir);
         }
         let k = k.sqrt();
@@ -68,4 +249,36 @@ impl Shape for Sphere {
     fn norm(&self, point: &Vector) -> Vector {
         (point - &self.center).norm()
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::around(&self.center, self.radius2.sqrt()))
+    }
+
+    /// The same arithmetic as intersec, but every step is done for all the lanes
+    fn intersec_packet(&self, packet: &RayPacket) -> [Option<f32>; LANES] {
+        let (orig, [dx, dy, dz]) = (packet.orig(), packet.dir());
+        let c = self.center.to_arr();
+        let [ox, oy, oz] = [0, 1, 2].map(|axis| lanes(|l| c[axis] - orig[axis][l]));
+        let oc_dir = lanes(|l| ox[l] * dx[l] + oy[l] * dy[l] + oz[l] * dz[l]);
+        // distance from the center to the ray squared by the cross product
+        let cx = lanes(|l| oy[l] * dz[l] - oz[l] * dy[l]);
+        let cy = lanes(|l| -ox[l] * dz[l] + oz[l] * dx[l]);
+        let cz = lanes(|l| ox[l] * dy[l] - oy[l] * dx[l]);
+        let k = lanes(|l| self.radius2 - (cx[l] * cx[l] + cy[l] * cy[l] + cz[l] * cz[l]));
+        let sq = lanes(|l| k[l].max(0.0).sqrt());
+        let near = lanes(|l| oc_dir[l] - sq[l]);
+        let far = lanes(|l| oc_dir[l] + sq[l]);
+
+        std::array::from_fn(|l| {
+            if l >= packet.count() || k[l] < 0.0 {
+                None
+            } else if k[l] == 0.0 && oc_dir[l] > 0.0 {
+                Some(oc_dir[l])
+            } else if near[l] > 0.0 && far[l] > 0.0 {
+                Some(near[l].min(far[l]))
+            } else {
+                None
+            }
+        })
+    }
 }
@@ -0,0 +1,229 @@
+use crate::raytracer::scene::shape::MeshData;
+use crate::raytracer::vector::Vector;
+
+/// Bicubic Bézier patch given by the grid 4x4 of the control points, row by row.
+pub struct Patch {
+    pub points: [Vector; 16],
+}
+
+impl Patch {
+    /// Return the point of the surface and its normal at the parameters (u, v) in [0, 1]
+    fn eval(&self, u: f32, v: f32) -> (Vector, Vector) {
+        let (bu, du) = bernstein(u);
+        let (bv, dv) = bernstein(v);
+        let mut p = Vector::new(0.0, 0.0, 0.0);
+        let mut pu = Vector::new(0.0, 0.0, 0.0);
+        let mut pv = Vector::new(0.0, 0.0, 0.0);
+        for (i, row) in self.points.chunks_exact(4).enumerate() {
+            for (j, c) in row.iter().enumerate() {
+                p += &((bu[i] * bv[j]) * c);
+                pu += &((du[i] * bv[j]) * c);
+                pv += &((bu[i] * dv[j]) * c);
+            }
+        }
+        (p, pu.cross(&pv))
+    }
+
+    /// Return the point and the normal, the normal at degenerate points (where an edge of the
+    /// patch is collapsed into a point) is taken a bit inside the patch
+    fn eval_norm(&self, u: f32, v: f32) -> (Vector, Vector) {
+        const INSIDE: f32 = 1e-3;
+        let (p, n) = self.eval(u, v);
+        if n.dot2() > f32::EPSILON * f32::EPSILON {
+            return (p, n.norm());
+        }
+        let (_, n) = self.eval(u.clamp(INSIDE, 1.0 - INSIDE), v.clamp(INSIDE, 1.0 - INSIDE));
+        (p, n.norm())
+    }
+
+    /// Return the numbers of segments along u and v needed to keep the triangles closer to
+    /// the surface than the tolerance, at least two so the patch has inner vertices
+    fn segments(&self, tolerance: f32) -> (usize, usize) {
+        let p = &self.points;
+        let mut n = (2, 2);
+        for k in 0..4 {
+            n.0 = n.0.max(curve_segments(
+                [&p[k], &p[4 + k], &p[8 + k], &p[12 + k]],
+                tolerance,
+            ));
+            n.1 = n.1.max(curve_segments(
+                [&p[4 * k], &p[4 * k + 1], &p[4 * k + 2], &p[4 * k + 3]],
+                tolerance,
+            ));
+        }
+        n
+    }
+
+    /// Add vertices and triangles of the patch to the mesh.
+    ///
+    /// Inside the patch is a grid by the segments along u and v, every edge is cut by the
+    /// segments of its own curve and is joined to the nearest row of the grid by a strip of
+    /// triangles. The curve of the edge is the same for the neighbour patch, so their edges
+    /// match without cracks whatever the segments inside are.
+    fn tessellate(&self, tolerance: f32, data: &mut MeshData) {
+        let p = &self.points;
+        let (nu, nv) = self.segments(tolerance);
+        let mut vertex = |u: f32, v: f32| {
+            let (point, norm) = self.eval_norm(u, v);
+            data.vertices.push(point);
+            data.normals.push(norm);
+            Sample {
+                index: data.vertices.len() - 1,
+                uv: [u, v],
+            }
+        };
+
+        let corners = [
+            vertex(0.0, 0.0),
+            vertex(1.0, 0.0),
+            vertex(0.0, 1.0),
+            vertex(1.0, 1.0),
+        ];
+        let mut edge = |curve: [&Vector; 4], from: usize, to: usize| {
+            let n = curve_segments(curve, tolerance);
+            let (a, b) = (corners[from].uv, corners[to].uv);
+            let mut line = vec![corners[from].clone()];
+            for k in 1..n {
+                let t = k as f32 / n as f32;
+                line.push(vertex(a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])));
+            }
+            line.push(corners[to].clone());
+            line
+        };
+        let edges = [
+            edge([&p[0], &p[1], &p[2], &p[3]], 0, 2),
+            edge([&p[12], &p[13], &p[14], &p[15]], 1, 3),
+            edge([&p[0], &p[4], &p[8], &p[12]], 0, 1),
+            edge([&p[3], &p[7], &p[11], &p[15]], 2, 3),
+        ];
+        let grid: Vec<Vec<Sample>> = (1..nu)
+            .map(|i| {
+                (1..nv)
+                    .map(|j| vertex(i as f32 / nu as f32, j as f32 / nv as f32))
+                    .collect()
+            })
+            .collect();
+
+        let mut faces = Vec::new();
+        let row = |i: usize| grid[i].clone();
+        let column = |j: usize| grid.iter().map(|r| r[j].clone()).collect::<Vec<_>>();
+        zip(&edges[0], &row(0), 1, &mut faces);
+        zip(&edges[1], &row(nu - 2), 1, &mut faces);
+        zip(&edges[2], &column(0), 0, &mut faces);
+        zip(&edges[3], &column(nv - 2), 0, &mut faces);
+        for i in 0..nu - 2 {
+            for j in 0..nv - 2 {
+                let (a, b) = (&grid[i][j], &grid[i + 1][j]);
+                let (c, d) = (&grid[i + 1][j + 1], &grid[i][j + 1]);
+                faces.push([a.clone(), b.clone(), c.clone()]);
+                faces.push([a.clone(), c.clone(), d.clone()]);
+            }
+        }
+        // triangles go counterclockwise in (u, v), so they face along the normal
+        data.faces.extend(faces.iter().map(|[a, b, c]| {
+            let (ab, ac) = (sub(b.uv, a.uv), sub(c.uv, a.uv));
+            if ab[0] * ac[1] - ab[1] * ac[0] >= 0.0 {
+                [a.index, b.index, c.index]
+            } else {
+                [a.index, c.index, b.index]
+            }
+        }));
+    }
+}
+
+/// Vertex of the mesh at the parameters of the patch
+#[derive(Clone)]
+struct Sample {
+    index: usize,
+    uv: [f32; 2],
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+/// Return the number of segments of the cubic curve needed to keep its chords closer to it
+/// than the tolerance
+fn curve_segments(p: [&Vector; 4], tolerance: f32) -> usize {
+    // deviation of the cubic from its chord with n segments is at most 3/4 of the max
+    // second difference of the control points divided by n^2
+    let second = |a: &Vector, b: &Vector, c: &Vector| (&(a - b) + &(c - b)).size();
+    let max = second(p[0], p[1], p[2]).max(second(p[1], p[2], p[3]));
+    ((0.75 * max / tolerance).sqrt().ceil() as usize).clamp(1, 64)
+}
+
+/// Join the edge and the row of the grid along it by triangles, the lines go in the same
+/// direction and are ordered by the parameter of the axis
+fn zip(edge: &[Sample], row: &[Sample], axis: usize, faces: &mut Vec<[Sample; 3]>) {
+    let (mut a, mut b) = (0, 0);
+    while a + 1 < edge.len() || b + 1 < row.len() {
+        // step along the line whose next vertex comes first
+        if b + 1 == row.len() || (a + 1 < edge.len() && edge[a + 1].uv[axis] <= row[b + 1].uv[axis])
+        {
+            faces.push([edge[a].clone(), edge[a + 1].clone(), row[b].clone()]);
+            a += 1;
+        } else {
+            faces.push([edge[a].clone(), row[b + 1].clone(), row[b].clone()]);
+            b += 1;
+        }
+    }
+}
+
+/// Return the cubic Bernstein polynomials and their derivatives at t
+fn bernstein(t: f32) -> ([f32; 4], [f32; 4]) {
+    let s = 1.0 - t;
+    (
+        [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t],
+        [
+            -3.0 * s * s,
+            3.0 * s * s - 6.0 * t * s,
+            6.0 * t * s - 3.0 * t * t,
+            3.0 * t * t,
+        ],
+    )
+}
+
+/// Tessellate the patches into triangles with normals of the surface in the vertices.
+///
+/// Every patch is cut by the segments needed by its own curvature, flat patches get few
+/// triangles next to curved ones.
+pub fn tessellate(patches: &[Patch], tolerance: f32) -> MeshData {
+    let mut data = MeshData::default();
+    for patch in patches.iter() {
+        patch.tessellate(tolerance, &mut data);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::scene::shape::bezier::{tessellate, Patch};
+    use crate::raytracer::vector::Vector;
+
+    #[test]
+    fn flat_and_curved_patches_share_edge_vertices() {
+        let patch = |x0: f32, bump: f32| Patch {
+            points: std::array::from_fn(|k| {
+                let (i, j) = (k / 4, k % 4);
+                let inner = (1..3).contains(&i) && (1..3).contains(&j);
+                Vector::new(x0 + i as f32, j as f32, if inner { bump } else { 0.0 })
+            }),
+        };
+        // the curved patch starts at the last row of the flat one
+        let flat = tessellate(&[patch(0.0, 0.0)], 0.01);
+        let curved = tessellate(&[patch(3.0, 2.0)], 0.01);
+        assert!(flat.faces.len() < curved.faces.len());
+
+        let on_edge = |vertices: &[Vector]| {
+            let mut y: Vec<f32> = vertices
+                .iter()
+                .map(|v| v.to_arr())
+                .filter(|v| v[0] == 3.0)
+                .map(|v| v[1])
+                .collect();
+            y.sort_by(f32::total_cmp);
+            y
+        };
+        assert_eq!(on_edge(&flat.vertices), on_edge(&curved.vertices));
+    }
+}
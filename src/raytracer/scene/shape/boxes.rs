@@ -0,0 +1,155 @@
+use crate::raytracer::matrix::Matrix;
+use crate::raytracer::packet::{RayPacket, LANES};
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{Aabb, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Box with faces parallel to the coordinate planes given by its min and max corners.
+pub struct AaBox {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+pub fn new_aabox(min: Vector, max: Vector) -> impl Shape {
+    let (min, max) = (min.to_arr(), max.to_arr());
+    AaBox {
+        min: [0, 1, 2].map(|i| f32::min(min[i], max[i])),
+        max: [0, 1, 2].map(|i| f32::max(min[i], max[i])),
+    }
+}
+
+impl Shape for AaBox {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        slabs(
+            &ray.get_orig().to_arr(),
+            &ray.get_dir().to_arr(),
+            &self.min,
+            &self.max,
+        )
+    }
+
+    fn norm(&self, point: &Vector) -> Vector {
+        let p = point.to_arr();
+        let mut n = [0.0; 3];
+        let (axis, sign) = nearest_face(|i| (p[i] - self.min[i], self.max[i] - p[i]));
+        n[axis] = sign;
+        Vector::new_from_arr(&n)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb {
+            min: self.min,
+            max: self.max,
+        })
+    }
+
+    /// The same slabs as intersec, but for all the lanes at once
+    fn intersec_packet(&self, packet: &RayPacket) -> [Option<f32>; LANES] {
+        let b = Aabb {
+            min: self.min,
+            max: self.max,
+        };
+        let (t_near, t_far) =
+            b.clip_packet(packet, [f32::NEG_INFINITY; LANES], [f32::INFINITY; LANES]);
+        std::array::from_fn(|l| {
+            let hit = l < packet.count()
+                && t_near[l] <= t_far[l]
+                && !t_far[l].is_infinite()
+                && t_near[l] > 0.0;
+            hit.then_some(t_near[l])
+        })
+    }
+}
+
+/// Box given by its center, half sizes along its own axes and rotation of the axes.
+pub struct OrientedBox {
+    center: Vector,
+    half: [f32; 3],
+    axes: [Vector; 3],
+}
+
+/// Rotation is Euler angles in degrees around X, Y and Z axes applied in this order.
+pub fn new_oriented_box(center: Vector, half_extents: [f32; 3], rotation: [f32; 3]) -> impl Shape {
+    let rotation = Matrix::rotation(&rotation);
+    OrientedBox {
+        center,
+        half: half_extents.map(f32::abs),
+        axes: [
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+        ]
+        .map(|a| rotation.vector(&a)),
+    }
+}
+
+impl OrientedBox {
+    /// Return coordinates of the Vector in the box's own axes
+    fn to_local(&self, v: &Vector) -> [f32; 3] {
+        [0, 1, 2].map(|i| self.axes[i].dot(v))
+    }
+}
+
+impl Shape for OrientedBox {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let orig = self.to_local(&(ray.get_orig() - &self.center));
+        let dir = self.to_local(ray.get_dir());
+        let min = self.half.map(|h| -h);
+        slabs(&orig, &dir, &min, &self.half)
+    }
+
+    fn norm(&self, point: &Vector) -> Vector {
+        let p = self.to_local(&(point - &self.center));
+        let (axis, sign) = nearest_face(|i| (p[i] + self.half[i], self.half[i] - p[i]));
+        sign * &self.axes[axis]
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let extent = (0..3)
+            .map(|i| self.axes[i].to_arr().map(|c| c.abs() * self.half[i]))
+            .fold([0.0; 3], |e, a| [0, 1, 2].map(|k| e[k] + a[k]));
+        let c = self.center.to_arr();
+        Some(Aabb {
+            min: [0, 1, 2].map(|k| c[k] - extent[k]),
+            max: [0, 1, 2].map(|k| c[k] + extent[k]),
+        })
+    }
+}
+
+/// Intersection of the ray with the box by the slabs method, the box axes are the
+/// coordinate axes.
+pub(super) fn slabs(orig: &[f32; 3], dir: &[f32; 3], min: &[f32; 3], max: &[f32; 3]) -> Intersec {
+    let mut t_near = f32::NEG_INFINITY;
+    let mut t_far = f32::INFINITY;
+    for i in 0..3 {
+        let inv = 1.0 / dir[i];
+        let t1 = (min[i] - orig[i]) * inv;
+        let t2 = (max[i] - orig[i]) * inv;
+        t_near = t_near.max(t1.min(t2));
+        t_far = t_far.min(t1.max(t2));
+    }
+
+    if t_near > t_far || t_far.is_infinite() {
+        return Intersec::None;
+    }
+    Intersec::Two(t_near, t_far)
+}
+
+/// Return the axis and the direction of the box face nearest to the point.
+/// dist returns distances from the point to the min and the max faces along the axis.
+fn nearest_face<F: Fn(usize) -> (f32, f32)>(dist: F) -> (usize, f32) {
+    let mut face = (0, -1.0);
+    let mut nearest = f32::MAX;
+    for i in 0..3 {
+        let (to_min, to_max) = dist(i);
+        if to_min.abs() < nearest {
+            nearest = to_min.abs();
+            face = (i, -1.0);
+        }
+        if to_max.abs() < nearest {
+            nearest = to_max.abs();
+            face = (i, 1.0);
+        }
+    }
+    face
+}
@@ -0,0 +1,544 @@
+//! Bounding volume hierarchy: tree of axis aligned boxes, so a ray is tested only against
+//! primitives whose boxes it crosses.
+
+use crate::raytracer::packet::{lanes, Lanes, RayPacket, LANES};
+use crate::raytracer::ray::Ray;
+use crate::raytracer::vector::Vector;
+
+/// Axis aligned bounding box
+#[derive(Clone, Debug)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: [f32::INFINITY; 3],
+            max: [f32::NEG_INFINITY; 3],
+        }
+    }
+
+    /// Box around the point with margin r
+    pub fn around(p: &Vector, r: f32) -> Aabb {
+        let p = p.to_arr();
+        Aabb {
+            min: p.map(|c| c - r),
+            max: p.map(|c| c + r),
+        }
+    }
+
+    /// Box around the disk with the unit normal
+    pub fn around_disk(center: &Vector, normal: &Vector, r: f32) -> Aabb {
+        let c = center.to_arr();
+        let extent = normal.to_arr().map(|n| r * (1.0 - n * n).max(0.0).sqrt());
+        Aabb {
+            min: [0, 1, 2].map(|i| c[i] - extent[i]),
+            max: [0, 1, 2].map(|i| c[i] + extent[i]),
+        }
+    }
+
+    /// Smallest box containing all the points
+    pub fn from_points<'a, I: IntoIterator<Item = &'a Vector>>(points: I) -> Aabb {
+        points
+            .into_iter()
+            .fold(Aabb::empty(), |b, p| b.union(&Aabb::around(p, 0.0)))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: [0, 1, 2].map(|i| f32::min(self.min[i], other.min[i])),
+            max: [0, 1, 2].map(|i| f32::max(self.max[i], other.max[i])),
+        }
+    }
+
+    pub fn intersection(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: [0, 1, 2].map(|i| f32::max(self.min[i], other.min[i])),
+            max: [0, 1, 2].map(|i| f32::min(self.max[i], other.max[i])),
+        }
+    }
+
+    /// Return the 8 corners of the box
+    pub fn corners(&self) -> [Vector; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|k| {
+            let pick = |i: usize| {
+                if k & (1 << i) == 0 {
+                    self.min[i]
+                } else {
+                    self.max[i]
+                }
+            };
+            Vector::new(pick(0), pick(1), pick(2))
+        })
+    }
+
+    fn center(&self, axis: usize) -> f32 {
+        0.5 * (self.min[axis] + self.max[axis])
+    }
+
+    /// Check if the box has no points, for example it's around the shape without surface
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| self.min[i] > self.max[i])
+    }
+
+    /// Half of the surface area, the chance of a random ray to hit the box is proportional
+    /// to it
+    pub fn half_area(&self) -> f32 {
+        let [x, y, z] = [0, 1, 2].map(|i| (self.max[i] - self.min[i]).max(0.0));
+        x * y + y * z + z * x
+    }
+
+    /// Return distances where the ray enters and leaves the box between 0 and t_max, None if
+    /// it misses the box, inv_dir is 1 / direction
+    pub fn clip(&self, orig: &[f32; 3], inv_dir: &[f32; 3], t_max: f32) -> Option<(f32, f32)> {
+        let mut t_near: f32 = 0.0;
+        let mut t_far = t_max;
+        for i in 0..3 {
+            let t1 = (self.min[i] - orig[i]) * inv_dir[i];
+            let t2 = (self.max[i] - orig[i]) * inv_dir[i];
+            // NaN from 0 * inf (the ray in the plane of the face) is ignored by min and max
+            t_near = t_near.max(t1.min(t2));
+            t_far = t_far.min(t1.max(t2));
+        }
+        (t_near <= t_far).then_some((t_near, t_far))
+    }
+
+    fn is_hit(&self, orig: &[f32; 3], inv_dir: &[f32; 3], t_max: f32) -> bool {
+        self.clip(orig, inv_dir, t_max).is_some()
+    }
+
+    /// Return distances where the rays of the packet enter and leave the box between t_near
+    /// and t_far, the ray misses the box if it enters farther than it leaves. The lanes are
+    /// computed all at once.
+    pub fn clip_packet(&self, packet: &RayPacket, t_near: Lanes, t_far: Lanes) -> (Lanes, Lanes) {
+        let (orig, inv_dir) = (packet.orig(), packet.inv_dir());
+        let (mut t_near, mut t_far) = (t_near, t_far);
+        for i in 0..3 {
+            let t1 = lanes(|l| (self.min[i] - orig[i][l]) * inv_dir[i][l]);
+            let t2 = lanes(|l| (self.max[i] - orig[i][l]) * inv_dir[i][l]);
+            t_near = lanes(|l| t_near[l].max(t1[l].min(t2[l])));
+            t_far = lanes(|l| t_far[l].min(t1[l].max(t2[l])));
+        }
+        (t_near, t_far)
+    }
+
+    /// Check if any ray of the packet crosses the box closer than its t_max
+    fn is_hit_packet(&self, packet: &RayPacket, t_max: &Lanes) -> bool {
+        let (t_near, t_far) = self.clip_packet(packet, [0.0; LANES], *t_max);
+        (0..LANES).any(|l| t_near[l] <= t_far[l])
+    }
+}
+
+enum Node {
+    /// primitives from start to start + count in the list of indices
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        count: usize,
+    },
+    /// the left child goes right after its parent, centers of its primitives are less along
+    /// the axis of the split
+    Inner {
+        bounds: Aabb,
+        right: usize,
+        axis: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+pub struct Bvh {
+    nodes: Vec<Node>,
+    /// indices of the primitives ordered by leaves
+    indices: Vec<usize>,
+}
+
+/// Max number of primitives in the leaf
+const LEAF_SIZE: usize = 8;
+/// Number of the buckets of the primitive centers where split of the node is looked for
+const BINS: usize = 16;
+/// Cost of the visit of the inner node relative to the intersection of a primitive
+const TRAVERSAL_COST: f32 = 0.125;
+/// Distance returned by the visitor of the traversal to stop it
+const STOP: f32 = f32::NEG_INFINITY;
+
+impl Bvh {
+    /// Build the tree over primitives given by their bounding boxes.
+    ///
+    /// The nodes are split by the surface area heuristic: the split minimizes the expected
+    /// number of primitives a ray is tested against, which is the number of primitives in
+    /// the child weighted by the area of its box.
+    pub fn new(bounds: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            indices: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            bvh.build(bounds, 0, bounds.len());
+        }
+        bvh
+    }
+
+    /// Return the box around all the primitives, None if there are none
+    pub fn bounds(&self) -> Option<&Aabb> {
+        self.nodes.first().map(Node::bounds)
+    }
+
+    fn build(&mut self, bounds: &[Aabb], start: usize, end: usize) {
+        let node_bounds = self.indices[start..end]
+            .iter()
+            .fold(Aabb::empty(), |b, i| b.union(&bounds[*i]));
+        let (mid, axis) = match self.split(bounds, start, end, &node_bounds) {
+            Some(split) => split,
+            None => {
+                self.nodes.push(Node::Leaf {
+                    bounds: node_bounds,
+                    start,
+                    count: end - start,
+                });
+                return;
+            }
+        };
+
+        let node = self.nodes.len();
+        self.nodes.push(Node::Inner {
+            bounds: node_bounds,
+            right: 0,
+            axis,
+        });
+        self.build(bounds, start, mid);
+        let right_node = self.nodes.len();
+        if let Node::Inner { right, .. } = &mut self.nodes[node] {
+            *right = right_node;
+        }
+        self.build(bounds, mid, end);
+    }
+
+    /// Reorder the primitives of the node, so the children are [start, mid) and [mid, end),
+    /// and return mid and the axis of the split. None if the node is cheaper as the leaf.
+    fn split(
+        &mut self,
+        bounds: &[Aabb],
+        start: usize,
+        end: usize,
+        node: &Aabb,
+    ) -> Option<(usize, usize)> {
+        let count = end - start;
+        if count <= 1 {
+            return None;
+        }
+        let centers = self.indices[start..end].iter().fold(Aabb::empty(), |b, i| {
+            let c = [0, 1, 2].map(|axis| bounds[*i].center(axis));
+            b.union(&Aabb { min: c, max: c })
+        });
+        let bin = |i: usize, axis: usize| {
+            let extent = centers.max[axis] - centers.min[axis];
+            let k = (bounds[i].center(axis) - centers.min[axis]) / extent * BINS as f32;
+            (k as usize).min(BINS - 1)
+        };
+
+        // (cost, axis, the first bin of the right child)
+        let mut best: Option<(f32, usize, usize)> = None;
+        for axis in 0..3 {
+            if centers.max[axis] - centers.min[axis] <= 0.0 {
+                continue;
+            }
+            let mut bins = vec![(Aabb::empty(), 0); BINS];
+            for &i in self.indices[start..end].iter() {
+                let b = &mut bins[bin(i, axis)];
+                b.0 = b.0.union(&bounds[i]);
+                b.1 += 1;
+            }
+            // cost of the left children for the splits after every bin
+            let mut left = Vec::with_capacity(BINS - 1);
+            let (mut b, mut n) = (Aabb::empty(), 0);
+            for bin in bins[..BINS - 1].iter() {
+                b = b.union(&bin.0);
+                n += bin.1;
+                left.push(b.half_area() * n as f32);
+            }
+            let (mut b, mut n) = (Aabb::empty(), 0);
+            for k in (1..BINS).rev() {
+                b = b.union(&bins[k].0);
+                n += bins[k].1;
+                let cost = left[k - 1] + b.half_area() * n as f32;
+                if best.is_none_or(|(c, _, _)| cost < c) {
+                    best = Some((cost, axis, k));
+                }
+            }
+        }
+
+        let area = node.half_area();
+        match best {
+            Some((cost, axis, k)) => {
+                let cost = if area > 0.0 {
+                    TRAVERSAL_COST + cost / area
+                } else {
+                    f32::INFINITY
+                };
+                if count <= LEAF_SIZE && cost >= count as f32 {
+                    return None;
+                }
+                let mut mid = start;
+                for j in start..end {
+                    if bin(self.indices[j], axis) < k {
+                        self.indices.swap(mid, j);
+                        mid += 1;
+                    }
+                }
+                if mid == start || mid == end {
+                    return Some(self.split_median(bounds, start, end, node));
+                }
+                Some((mid, axis))
+            }
+            // all the centers are in the same point
+            None if count <= LEAF_SIZE => None,
+            None => Some(self.split_median(bounds, start, end, node)),
+        }
+    }
+
+    /// Split at the median of the centers along the longest axis, return mid and the axis
+    fn split_median(
+        &mut self,
+        bounds: &[Aabb],
+        start: usize,
+        end: usize,
+        node: &Aabb,
+    ) -> (usize, usize) {
+        let axis = (0..3)
+            .max_by(|&a, &b| {
+                let size = |i: usize| node.max[i] - node.min[i];
+                size(a).total_cmp(&size(b))
+            })
+            .unwrap_or(0);
+        let mid = (start + end) / 2;
+        self.indices[start..end].select_nth_unstable_by(mid - start, |a, b| {
+            bounds[*a].center(axis).total_cmp(&bounds[*b].center(axis))
+        });
+        (mid, axis)
+    }
+
+    /// Return the primitive hit by the ray nearest to its origin and the distance to the
+    /// hit, intersec returns the distance to the hit of the primitive
+    pub fn closest<F: Fn(usize) -> Option<f32>>(
+        &self,
+        ray: &Ray,
+        intersec: F,
+    ) -> Option<(usize, f32)> {
+        self.closest_within(ray, f32::INFINITY, intersec)
+    }
+
+    /// Return the nearest primitive hit by the ray closer than t_max, boxes farther than
+    /// t_max aren't visited
+    pub fn closest_within<F: Fn(usize) -> Option<f32>>(
+        &self,
+        ray: &Ray,
+        t_max: f32,
+        intersec: F,
+    ) -> Option<(usize, f32)> {
+        self.closest_with(ray, t_max, |i| intersec(i).map(|t| (t, ())))
+            .map(|(i, t, _)| (i, t))
+    }
+
+    /// Return the nearest primitive hit by the ray closer than t_max, the distance to the
+    /// hit and what intersec found along with the distance, so the hit isn't computed again
+    pub fn closest_with<T, F: Fn(usize) -> Option<(f32, T)>>(
+        &self,
+        ray: &Ray,
+        t_max: f32,
+        intersec: F,
+    ) -> Option<(usize, f32, T)> {
+        let mut nearest: Option<(usize, f32, T)> = None;
+        self.traverse(ray, t_max, |i, t_max| {
+            if let Some((t, found)) = intersec(i) {
+                if t < t_max {
+                    nearest = Some((i, t, found));
+                    return Some(t);
+                }
+            }
+            None
+        });
+        nearest
+    }
+
+    /// Return the primitives hit by the rays of the packet nearest to their origins and the
+    /// distances to the hits, intersec returns distances to the hits of the primitive by the
+    /// rays. The rays go down the tree together while any of them crosses the boxes, so
+    /// coherent rays share the traversal.
+    pub fn closest_packet<F: Fn(usize) -> [Option<f32>; LANES]>(
+        &self,
+        packet: &RayPacket,
+        intersec: F,
+    ) -> [Option<(usize, f32)>; LANES] {
+        let mut nearest = [None; LANES];
+        if self.nodes.is_empty() {
+            return nearest;
+        }
+        // free lanes of the packet never cross boxes
+        let mut t_max = lanes(|l| {
+            if l < packet.count() {
+                f32::INFINITY
+            } else {
+                f32::NEG_INFINITY
+            }
+        });
+
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if !node.bounds().is_hit_packet(packet, &t_max) {
+                continue;
+            }
+            match node {
+                Node::Leaf { start, count, .. } => {
+                    for &i in self.indices[*start..start + count].iter() {
+                        for (l, t) in intersec(i).into_iter().enumerate() {
+                            match t {
+                                Some(t) if t < t_max[l] => {
+                                    t_max[l] = t;
+                                    nearest[l] = Some((i, t));
+                                }
+                                _ => (),
+                            }
+                        }
+                    }
+                }
+                Node::Inner { right, axis, .. } => {
+                    // coherent rays of the packet go the same way as the first one
+                    let (near, far) = order(n, *right, packet.dir()[*axis][0]);
+                    stack.push(far);
+                    stack.push(near);
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Check if any primitive is hit by the ray closer than t_max, it stops at the first
+    /// found hit, so it's faster than closest for shadows
+    pub fn any_hit<F: Fn(usize) -> Option<f32>>(&self, ray: &Ray, t_max: f32, intersec: F) -> bool {
+        let mut found = false;
+        self.traverse(ray, t_max, |i, _| {
+            if intersec(i).is_some_and(|t| t <= t_max) {
+                found = true;
+                return Some(STOP);
+            }
+            None
+        });
+        found
+    }
+
+    /// Visit the primitives in the boxes crossed by the ray closer than the nearest hit
+    /// (t_max at first), visit gets the primitive and the distance to the nearest hit and
+    /// returns the new one, the traversal stops if it's STOP
+    fn traverse<F: FnMut(usize, f32) -> Option<f32>>(&self, ray: &Ray, t_max: f32, mut visit: F) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let orig = ray.get_orig().to_arr();
+        let inv_dir = ray.get_dir().to_arr().map(|d| 1.0 / d);
+
+        let mut t_max = t_max;
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if !node.bounds().is_hit(&orig, &inv_dir, t_max) {
+                continue;
+            }
+            match node {
+                Node::Leaf { start, count, .. } => {
+                    for &i in self.indices[*start..start + count].iter() {
+                        if let Some(t) = visit(i, t_max) {
+                            if t == STOP {
+                                return;
+                            }
+                            t_max = t;
+                        }
+                    }
+                }
+                Node::Inner { right, axis, .. } => {
+                    // the nearer child goes first, so its hits cut boxes of the far one
+                    let (near, far) = order(n, *right, inv_dir[*axis]);
+                    stack.push(far);
+                    stack.push(near);
+                }
+            }
+        }
+    }
+}
+
+/// Return the near and the far children of the inner node n for the direction along the
+/// axis of its split
+fn order(n: usize, right: usize, dir: f32) -> (usize, usize) {
+    if dir < 0.0 {
+        (right, n + 1)
+    } else {
+        (n + 1, right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use crate::raytracer::ray::Ray;
+    use crate::raytracer::scene::shape::bvh::{Aabb, Bvh};
+    use crate::raytracer::vector::Vector;
+
+    /// Return the distance to the point where the ray enters the box
+    fn enter(b: &Aabb, ray: &Ray) -> Option<f32> {
+        let (orig, dir) = (ray.get_orig().to_arr(), ray.get_dir().to_arr());
+        let (mut t_near, mut t_far) = (0.0_f32, f32::INFINITY);
+        for i in 0..3 {
+            let t1 = (b.min[i] - orig[i]) / dir[i];
+            let t2 = (b.max[i] - orig[i]) / dir[i];
+            t_near = t_near.max(t1.min(t2));
+            t_far = t_far.min(t1.max(t2));
+        }
+        (t_near <= t_far).then_some(t_near)
+    }
+
+    #[test]
+    fn same_hits_as_linear_search() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut v = |k: f32| {
+            Vector::new(rng.gen(), rng.gen(), rng.gen())
+                .to_arr()
+                .map(|c| k * c)
+        };
+        let boxes: Vec<Aabb> = (0..500)
+            .map(|_| {
+                let (p, s) = (v(20.0), v(1.0));
+                Aabb {
+                    min: p,
+                    max: [0, 1, 2].map(|i| p[i] + s[i]),
+                }
+            })
+            .collect();
+        let bvh = Bvh::new(&boxes);
+
+        for _ in 0..200 {
+            let ray = Ray::new(
+                Vector::new_from_arr(&v(20.0)),
+                Vector::new_from_arr(&v(1.0).map(|c| c - 0.5)).norm(),
+            );
+            let linear = (0..boxes.len())
+                .filter_map(|i| enter(&boxes[i], &ray).map(|t| (i, t)))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let found = bvh.closest(&ray, |i| enter(&boxes[i], &ray));
+            assert_eq!(found.map(|(_, t)| t), linear.map(|(_, t)| t));
+
+            let t_max = linear.map_or(1.0, |(_, t)| t + 0.5);
+            assert!(bvh.any_hit(&ray, t_max, |i| enter(&boxes[i], &ray)) == linear.is_some());
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{face_forward, Aabb, Hit, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Cone cut by two planes orthogonal to its axis, given by the centers of the end disks and
+/// their radii. Cylinder is the cone with the same radii.
+///
+/// With caps the cone is a solid and the ray enters and leaves it, otherwise it's a tube
+/// which can be seen from inside.
+pub struct Cone {
+    start: Vector,
+    /// unit Vector of the axis from start to end
+    axis: Vector,
+    height: f32,
+    start_radius: f32,
+    /// change of the radius along the axis per unit of the height
+    slope: f32,
+    caps: bool,
+}
+
+pub fn new_cylinder(start: Vector, end: Vector, radius: f32, caps: bool) -> impl Shape {
+    new_cone(start, end, radius, radius, caps)
+}
+
+pub fn new_cone(
+    start: Vector,
+    end: Vector,
+    start_radius: f32,
+    end_radius: f32,
+    caps: bool,
+) -> impl Shape {
+    let axis = &end - &start;
+    let height = axis.size();
+    Cone {
+        start,
+        axis: axis.norm(),
+        height,
+        start_radius: start_radius.abs(),
+        slope: (end_radius.abs() - start_radius.abs()) / height,
+        caps,
+    }
+}
+
+impl Cone {
+    fn radius(&self, h: f32) -> f32 {
+        self.start_radius + self.slope * h
+    }
+
+    /// Return distances to the side surface between the end planes
+    fn intersec_side(&self, ray: &Ray, hits: &mut Vec<f32>) {
+        let oc = ray.get_orig() - &self.start;
+        let d = ray.get_dir();
+        let hc = oc.dot(&self.axis);
+        let hd = d.dot(&self.axis);
+        let rc = self.radius(hc);
+        let k2 = 1.0 + self.slope * self.slope;
+
+        let a = d.dot2() - k2 * hd * hd;
+        let b = oc.dot(d) - hc * hd - rc * self.slope * hd;
+        let c = oc.dot2() - hc * hc - rc * rc;
+        if a.abs() < f32::EPSILON {
+            return;
+        }
+
+        let disc = b * b - a * c;
+        if disc < 0.0 {
+            return;
+        }
+        let disc = disc.sqrt();
+        for t in [(-b - disc) / a, (-b + disc) / a] {
+            let h = hc + t * hd;
+            if (0.0..=self.height).contains(&h) {
+                hits.push(t);
+            }
+        }
+    }
+
+    /// Return distances to the end disks
+    fn intersec_caps(&self, ray: &Ray, hits: &mut Vec<f32>) {
+        let hd = ray.get_dir().dot(&self.axis);
+        if hd.abs() < f32::EPSILON {
+            return;
+        }
+        let hc = (ray.get_orig() - &self.start).dot(&self.axis);
+        for h in [0.0, self.height] {
+            let t = (h - hc) / hd;
+            let q = &ray.point_on_ray(t) - &self.start;
+            let radial = &q - &(h * &self.axis);
+            let r = self.radius(h);
+            if radial.dot2() <= r * r {
+                hits.push(t);
+            }
+        }
+    }
+}
+
+impl Shape for Cone {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let mut hits = Vec::with_capacity(4);
+        self.intersec_side(ray, &mut hits);
+        if self.caps {
+            self.intersec_caps(ray, &mut hits);
+        }
+        hits.sort_by(f32::total_cmp);
+
+        if self.caps {
+            // the ray enters and leaves the solid
+            return match (hits.first(), hits.last()) {
+                (Some(&t1), Some(&t2)) if hits.len() > 1 => Intersec::Two(t1, t2),
+                _ => Intersec::None,
+            };
+        }
+        hits.retain(|&t| t > 0.0);
+        match hits[..] {
+            [] => Intersec::None,
+            [t] => Intersec::One(t),
+            [t1, t2, ..] => Intersec::Two(t1, t2),
+        }
+    }
+
+    fn norm(&self, point: &Vector) -> Vector {
+        let q = point - &self.start;
+        let h = q.dot(&self.axis);
+        let radial = &q - &(h * &self.axis);
+
+        if self.caps {
+            let to_side = (radial.size() - self.radius(h)).abs();
+            if h.abs() < to_side {
+                return -1.0 * &self.axis;
+            }
+            if (self.height - h).abs() < to_side {
+                return self.axis.clone();
+            }
+        }
+        (radial.norm() + &(-self.slope * &self.axis)).norm()
+    }
+
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        let point = ray.point_on_ray(distance);
+        let norm = self.norm(&point);
+        // tube without caps is lit from inside too
+        let norm = if self.caps {
+            norm
+        } else {
+            face_forward(norm, ray)
+        };
+        Hit {
+            point,
+            norm,
+            shading_norm: None,
+            color: None,
+            uv: None,
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let end = &self.start + &(self.height * &self.axis);
+        let start = Aabb::around_disk(&self.start, &self.axis, self.start_radius);
+        Some(start.union(&Aabb::around_disk(
+            &end,
+            &self.axis,
+            self.radius(self.height),
+        )))
+    }
+}
@@ -0,0 +1,189 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{Aabb, Hit, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+#[derive(Clone, Copy)]
+pub enum Operation {
+    Union,
+    Intersection,
+    /// left shape without right one
+    Difference,
+}
+
+/// Constructive solid geometry: solid made of two shapes by the operation on the intervals
+/// of the ray inside them.
+///
+/// The shapes should be solids (spheres, boxes, tori, capped cylinders and cones or other
+/// CSG), flat shapes have no inside and don't carve anything.
+pub struct Csg {
+    operation: Operation,
+    left: Box<dyn Shape>,
+    right: Box<dyn Shape>,
+}
+
+pub fn new_csg(operation: Operation, left: Box<dyn Shape>, right: Box<dyn Shape>) -> impl Shape {
+    Csg {
+        operation,
+        left,
+        right,
+    }
+}
+
+impl Csg {
+    /// Return child shape on which surface the point is and whether its normal looks inside
+    /// the result solid
+    fn surface_of(&self, point: &Vector) -> (&dyn Shape, bool) {
+        let flip_right = matches!(self.operation, Operation::Difference);
+        if is_on_surface(self.left.as_ref(), point) {
+            (self.left.as_ref(), false)
+        } else {
+            (self.right.as_ref(), flip_right)
+        }
+    }
+}
+
+impl Shape for Csg {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let left = self.left.intersec(ray).into_intervals();
+        let right = self.right.intersec(ray).into_intervals();
+        let intervals = match self.operation {
+            Operation::Union => union(left, right),
+            Operation::Intersection => intersection(&left, &right),
+            Operation::Difference => difference(&left, &right),
+        };
+        Intersec::from_intervals(intervals)
+    }
+
+    fn norm(&self, point: &Vector) -> Vector {
+        let (shape, flip) = self.surface_of(point);
+        let n = shape.norm(point);
+        if flip {
+            -1.0 * &n
+        } else {
+            n
+        }
+    }
+
+    /// The hit is on the surface of the child which has the boundary of its interval at
+    /// the distance
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        let gap = |intervals: Vec<(f32, f32)>| {
+            intervals
+                .into_iter()
+                .flat_map(|(d1, d2)| [d1, d2])
+                .map(|d| (d - distance).abs())
+                .fold(f32::MAX, f32::min)
+        };
+        let left_gap = gap(self.left.intersec(ray).into_intervals());
+        let right_gap = gap(self.right.intersec(ray).into_intervals());
+
+        if left_gap <= right_gap {
+            return self.left.hit(ray, distance);
+        }
+        let mut hit = self.right.hit(ray, distance);
+        if let Operation::Difference = self.operation {
+            hit.norm = -1.0 * &hit.norm;
+            hit.shading_norm = hit.shading_norm.map(|n| -1.0 * &n);
+        }
+        hit
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let (left, right) = (self.left.bounds(), self.right.bounds());
+        match self.operation {
+            Operation::Union => Some(left?.union(&right?)),
+            Operation::Intersection => match (left, right) {
+                (Some(l), Some(r)) => Some(l.intersection(&r)),
+                (l, r) => l.or(r),
+            },
+            Operation::Difference => left,
+        }
+    }
+}
+
+/// Check if the point is on the surface of the shape: short ray going through the point
+/// along the normal crosses the surface near the point.
+fn is_on_surface(shape: &dyn Shape, point: &Vector) -> bool {
+    const PROBE: f32 = 1e-3;
+
+    let n = shape.norm(point);
+    let ray = Ray::new(point + &(-PROBE * &n), n);
+    shape
+        .intersec(&ray)
+        .into_intervals()
+        .into_iter()
+        .flat_map(|(d1, d2)| [d1, d2])
+        .any(|d| (d - PROBE).abs() < 0.5 * PROBE)
+}
+
+fn union(mut left: Vec<(f32, f32)>, right: Vec<(f32, f32)>) -> Vec<(f32, f32)> {
+    left.extend(right);
+    left.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut res: Vec<(f32, f32)> = Vec::with_capacity(left.len());
+    for (d1, d2) in left {
+        match res.last_mut() {
+            Some(last) if d1 <= last.1 => last.1 = last.1.max(d2),
+            _ => res.push((d1, d2)),
+        }
+    }
+    res
+}
+
+fn intersection(left: &[(f32, f32)], right: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let mut res = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        let (l, r) = (left[i], right[j]);
+        let (d1, d2) = (l.0.max(r.0), l.1.min(r.1));
+        if d1 <= d2 {
+            res.push((d1, d2));
+        }
+        if l.1 < r.1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    res
+}
+
+fn difference(left: &[(f32, f32)], right: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let mut res = Vec::new();
+    for &(mut d1, d2) in left.iter() {
+        for &(r1, r2) in right.iter() {
+            if r2 < d1 || r1 > d2 {
+                continue;
+            }
+            if r1 > d1 {
+                res.push((d1, r1));
+            }
+            d1 = r2;
+        }
+        if d1 < d2 {
+            res.push((d1, d2));
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::scene::shape::csg::{difference, intersection, union};
+
+    #[test]
+    fn operations_on_intervals() {
+        let left = vec![(1.0, 4.0), (6.0, 8.0)];
+        let right = vec![(2.0, 3.0), (5.0, 7.0)];
+
+        assert_eq!(
+            union(left.clone(), right.clone()),
+            vec![(1.0, 4.0), (5.0, 8.0)]
+        );
+        assert_eq!(intersection(&left, &right), vec![(2.0, 3.0), (6.0, 7.0)]);
+        assert_eq!(
+            difference(&left, &right),
+            vec![(1.0, 2.0), (3.0, 4.0), (7.0, 8.0)]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::bvh::{Aabb, Bvh};
+use crate::raytracer::scene::shape::{face_forward, Hit, Intersec, Shape, HIT_TOLERANCE};
+use crate::raytracer::vector::Vector;
+
+/// Cubic Bézier curve with the width changing linearly from its start to its end
+pub struct Curve {
+    pub points: [Vector; 4],
+    pub width: [f32; 2],
+}
+
+#[derive(Clone, Copy)]
+pub enum CurveKind {
+    /// flat strip turned to the ray
+    Ribbon,
+    /// round tube
+    Tube,
+}
+
+/// Straight piece of the curve, its radius changes linearly from start to end
+struct Segment {
+    start: Vector,
+    axis: Vector,
+    length: f32,
+    radius: [f32; 2],
+}
+
+/// Set of curves (strands of hair, grass or cables), every curve is cut into straight
+/// segments and the segments are put into the bounding volume hierarchy, so thousands of
+/// thin strands are traced fast.
+pub struct Curves {
+    segments: Vec<Segment>,
+    bvh: Bvh,
+    kind: CurveKind,
+}
+
+pub fn new_curves(curves: &[Curve], kind: CurveKind) -> Curves {
+    let segments: Vec<Segment> = curves.iter().flat_map(split).collect();
+    let bounds: Vec<Aabb> = segments
+        .iter()
+        .map(|s| {
+            let end = s.point(s.length);
+            Aabb::around(&s.start, s.radius[0]).union(&Aabb::around(&end, s.radius[1]))
+        })
+        .collect();
+    Curves {
+        bvh: Bvh::new(&bounds),
+        segments,
+        kind,
+    }
+}
+
+/// Cut the curve into segments, their number grows with the curvature, so the segments
+/// stay closer to the curve than a half of its radius
+fn split(curve: &Curve) -> Vec<Segment> {
+    let p = &curve.points;
+    let second = |i: usize| (&(&p[i] - &p[i + 1]) + &(&p[i + 2] - &p[i + 1])).size();
+    let tolerance = 0.25 * f32::max(curve.width[0], curve.width[1]).max(f32::EPSILON);
+    let n = ((0.75 * f32::max(second(0), second(1)) / tolerance)
+        .sqrt()
+        .ceil() as usize)
+        .clamp(1, 32);
+
+    let at = |t: f32| {
+        let s = 1.0 - t;
+        let b = [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t];
+        let mut point = Vector::new(0.0, 0.0, 0.0);
+        for (w, c) in b.iter().zip(p.iter()) {
+            point += &(*w * c);
+        }
+        let radius = 0.5 * (curve.width[0] * s + curve.width[1] * t);
+        (point, radius)
+    };
+    (0..n)
+        .filter_map(|i| {
+            let (a, ra) = at(i as f32 / n as f32);
+            let (b, rb) = at((i + 1) as f32 / n as f32);
+            let axis = &b - &a;
+            let length = axis.size();
+            (length > 0.0).then(|| Segment {
+                start: a,
+                axis: axis.norm(),
+                length,
+                radius: [ra, rb],
+            })
+        })
+        .collect()
+}
+
+impl Segment {
+    fn point(&self, s: f32) -> Vector {
+        &self.start + &(s * &self.axis)
+    }
+
+    /// Return the distance to the hit of the ray and the distance from the start of the
+    /// segment along its axis to the hit.
+    ///
+    /// The hit is near the point where the ray passes the axis at the closest distance, the
+    /// radius is taken at this point. Tube is hit where the ray enters the cylinder of this
+    /// radius, ribbon is hit at the closest point.
+    fn intersec(&self, ray: &Ray, kind: CurveKind) -> Option<(f32, f32)> {
+        let d = ray.get_dir();
+        let w = ray.get_orig() - &self.start;
+        // parts of the vectors across the axis
+        let d_across = d - &(d.dot(&self.axis) * &self.axis);
+        let w_across = &w - &(w.dot(&self.axis) * &self.axis);
+        let dd = d_across.dot2();
+        if dd < f32::EPSILON {
+            return None;
+        }
+
+        let t_closest = -w_across.dot(&d_across) / dd;
+        let dist2 = (&w_across + &(t_closest * &d_across)).dot2();
+        let s_closest = (w.dot(&self.axis) + t_closest * d.dot(&self.axis)) / self.length;
+        let k = s_closest.clamp(0.0, 1.0);
+        let r = self.radius[0] * (1.0 - k) + self.radius[1] * k;
+        if dist2 > r * r {
+            return None;
+        }
+
+        let t = match kind {
+            CurveKind::Ribbon => t_closest,
+            CurveKind::Tube => t_closest - ((r * r - dist2) / dd).sqrt(),
+        };
+        let s = w.dot(&self.axis) + t * d.dot(&self.axis);
+        if t <= 0.0 || s < 0.0 || s > self.length {
+            return None;
+        }
+        Some((t, s))
+    }
+}
+
+impl Shape for Curves {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        match self.bvh.closest(ray, |i| {
+            self.segments[i].intersec(ray, self.kind).map(|h| h.0)
+        }) {
+            Some((_, t)) => Intersec::One(t),
+            None => Intersec::None,
+        }
+    }
+
+    /// Return normal of the nearest segment going from its axis
+    fn norm(&self, point: &Vector) -> Vector {
+        let nearest = self.segments.iter().min_by(|a, b| {
+            let dist = |s: &Segment| {
+                let along = (point - &s.start).dot(&s.axis).clamp(0.0, s.length);
+                (point - &s.point(along)).dot2()
+            };
+            dist(a).total_cmp(&dist(b))
+        });
+        match nearest {
+            None => Vector::new(0.0, 0.0, 1.0),
+            Some(s) => {
+                let along = (point - &s.start).dot(&s.axis).clamp(0.0, s.length);
+                (point - &s.point(along)).norm()
+            }
+        }
+    }
+
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        let point = ray.point_on_ray(distance);
+        let t_max = distance + HIT_TOLERANCE * distance.abs().max(1.0);
+        let found = self
+            .bvh
+            .closest_with(ray, t_max, |i| self.segments[i].intersec(ray, self.kind));
+        let norm = match found {
+            None => self.norm(&point),
+            Some((i, _, s)) => {
+                let seg = &self.segments[i];
+                match self.kind {
+                    // the ribbon faces the ray
+                    CurveKind::Ribbon => {
+                        let d = ray.get_dir();
+                        let across = d - &(d.dot(&seg.axis) * &seg.axis);
+                        face_forward(across.norm(), ray)
+                    }
+                    CurveKind::Tube => (&point - &seg.point(s)).norm(),
+                }
+            }
+        };
+        Hit {
+            point,
+            norm,
+            shading_norm: None,
+            color: None,
+            uv: None,
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.bvh.bounds().cloned()
+    }
+}
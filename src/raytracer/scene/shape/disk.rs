@@ -0,0 +1,77 @@
+use std::f32::consts::PI;
+
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{face_forward, Aabb, Hit, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Flat disk given by its center, normal and radius, it is lit from both sides.
+pub struct Disk {
+    center: Vector,
+    normal: Vector,
+    radius: f32,
+    /// unit Vector in the disk plane from which the angle of the texture coordinates goes
+    tangent: Vector,
+}
+
+pub fn new_disk(center: Vector, normal: Vector, radius: f32) -> impl Shape {
+    let normal = normal.norm();
+    // any Vector not parallel to the normal gives the tangent
+    let [x, y, _] = normal.to_arr();
+    let other = if x.abs() < 0.9 && y.abs() < 0.9 {
+        Vector::new(1.0, 0.0, 0.0)
+    } else {
+        Vector::new(0.0, 0.0, 1.0)
+    };
+    Disk {
+        tangent: normal.cross(&other).norm(),
+        center,
+        normal,
+        radius: radius.abs(),
+    }
+}
+
+impl Disk {
+    /// Return texture coordinates of the point on the disk: angle around the center in
+    /// turns and distance from the center in radii
+    pub fn uv(&self, point: &Vector) -> (f32, f32) {
+        let p = point - &self.center;
+        let bitangent = self.normal.cross(&self.tangent);
+        let angle = p.dot(&bitangent).atan2(p.dot(&self.tangent));
+        let u = angle / (2.0 * PI);
+        (if u < 0.0 { u + 1.0 } else { u }, p.size() / self.radius)
+    }
+}
+
+impl Shape for Disk {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let denom = self.normal.dot(ray.get_dir());
+        if denom.abs() < f32::EPSILON {
+            return Intersec::None;
+        }
+
+        let t = (&self.center - ray.get_orig()).dot(&self.normal) / denom;
+        if t <= 0.0 || (&ray.point_on_ray(t) - &self.center).dot2() > self.radius * self.radius {
+            return Intersec::None;
+        }
+        Intersec::One(t)
+    }
+
+    fn norm(&self, _point: &Vector) -> Vector {
+        self.normal.clone()
+    }
+
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        let point = ray.point_on_ray(distance);
+        Hit {
+            norm: face_forward(self.normal.clone(), ray),
+            shading_norm: None,
+            color: None,
+            uv: Some(self.uv(&point)),
+            point,
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::around_disk(&self.center, &self.normal, self.radius))
+    }
+}
@@ -0,0 +1,191 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::boxes::slabs;
+use crate::raytracer::scene::shape::triangle::intersec_triangle;
+use crate::raytracer::scene::shape::{Aabb, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Grid of height samples in [0, 1], rows go along Z axis and columns along X axis
+pub struct HeightMap {
+    pub columns: usize,
+    pub rows: usize,
+    /// samples row by row
+    pub samples: Vec<f32>,
+}
+
+/// Terrain over the rectangle in the XZ plane, the surface goes up along Y axis by the
+/// heights of the samples and is made of two triangles between every four samples.
+pub struct Heightfield {
+    /// corner of the rectangle with min X and Z, its Y is the level of zero height
+    origin: Vector,
+    /// sizes of the cell between samples along X and Z
+    cell: [f32; 2],
+    columns: usize,
+    rows: usize,
+    /// heights of the samples above the origin
+    heights: Vec<f32>,
+    normals: Vec<Vector>,
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+/// size is the size of the rectangle along X and Z, height is the height of the sample 1.0.
+/// The map must have at least 2 rows and 2 columns.
+pub fn new_heightfield(map: HeightMap, origin: Vector, size: [f32; 2], height: f32) -> impl Shape {
+    let cell = [
+        size[0] / (map.columns - 1) as f32,
+        size[1] / (map.rows - 1) as f32,
+    ];
+    let heights: Vec<f32> = map.samples.iter().map(|h| h * height).collect();
+
+    let h = |i: usize, j: usize| heights[j * map.columns + i];
+    let mut normals = Vec::with_capacity(heights.len());
+    for j in 0..map.rows {
+        for i in 0..map.columns {
+            // central differences, one-sided ones at the border
+            let (i0, i1) = (i.saturating_sub(1), usize::min(i + 1, map.columns - 1));
+            let (j0, j1) = (j.saturating_sub(1), usize::min(j + 1, map.rows - 1));
+            let dx = (h(i1, j) - h(i0, j)) / ((i1 - i0) as f32 * cell[0]);
+            let dz = (h(i, j1) - h(i, j0)) / ((j1 - j0) as f32 * cell[1]);
+            normals.push(Vector::new(-dx, 1.0, -dz).norm());
+        }
+    }
+
+    // the bounding box is a bit thicker, so the flat terrain is hit too
+    const PAD: f32 = 1e-4;
+    let (low, high) = heights
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(l, h), &x| (l.min(x), h.max(x)));
+    let [x, y, z] = origin.to_arr();
+    Heightfield {
+        min: [x, y + low - PAD, z],
+        max: [x + size[0], y + high + PAD, z + size[1]],
+        origin,
+        cell,
+        columns: map.columns,
+        rows: map.rows,
+        heights,
+        normals,
+    }
+}
+
+impl Heightfield {
+    fn vertex(&self, i: usize, j: usize) -> Vector {
+        let offset = Vector::new(
+            i as f32 * self.cell[0],
+            self.heights[j * self.columns + i],
+            j as f32 * self.cell[1],
+        );
+        &self.origin + &offset
+    }
+
+    /// Return distance to the hit of the ray with the triangles of the cell
+    fn intersec_cell(&self, ray: &Ray, i: usize, j: usize) -> Option<f32> {
+        let v00 = self.vertex(i, j);
+        let v10 = self.vertex(i + 1, j);
+        let v01 = self.vertex(i, j + 1);
+        let v11 = self.vertex(i + 1, j + 1);
+        [(&v00, &v10, &v11), (&v00, &v11, &v01)]
+            .into_iter()
+            .filter_map(|(a, b, c)| intersec_triangle(ray, a, b, c))
+            .map(|(t, _, _)| t)
+            .min_by(f32::total_cmp)
+    }
+
+    /// Return the cell containing the point (X, Z) and the position of the point in it
+    fn cell_at(&self, x: f32, z: f32) -> ([usize; 2], [f32; 2]) {
+        let [ox, _, oz] = self.origin.to_arr();
+        let cells = [self.columns - 1, self.rows - 1];
+        let mut idx = [0; 2];
+        let mut frac = [0.0; 2];
+        for (k, g) in [(x - ox) / self.cell[0], (z - oz) / self.cell[1]]
+            .into_iter()
+            .enumerate()
+        {
+            let c = (g.floor().max(0.0) as usize).min(cells[k] - 1);
+            idx[k] = c;
+            frac[k] = (g - c as f32).clamp(0.0, 1.0);
+        }
+        (idx, frac)
+    }
+}
+
+impl Shape for Heightfield {
+    /// Walk the cells under the ray by 2D-DDA in the XZ plane from the point where the ray
+    /// enters the bounding box, the first cell with a hit has the nearest one
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let orig = ray.get_orig().to_arr();
+        let dir = ray.get_dir().to_arr();
+        let (t_enter, t_leave) = match slabs(&orig, &dir, &self.min, &self.max) {
+            Intersec::Two(t1, t2) if t2 > 0.0 => (t1.max(0.0), t2),
+            _ => return Intersec::None,
+        };
+
+        let p = ray.point_on_ray(t_enter).to_arr();
+        let ([mut i, mut j], _) = self.cell_at(p[0], p[2]);
+
+        // along X and Z: step between cells, distance between cell borders, distance to
+        // the next border
+        let axis = |k: usize, c: usize| {
+            let d = dir[2 * k];
+            let o = self.min[2 * k];
+            if d > 0.0 {
+                let border = o + (c + 1) as f32 * self.cell[k];
+                (1, self.cell[k] / d, (border - orig[2 * k]) / d)
+            } else if d < 0.0 {
+                let border = o + c as f32 * self.cell[k];
+                (-1, -self.cell[k] / d, (border - orig[2 * k]) / d)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let (step_i, delta_i, mut next_i) = axis(0, i);
+        let (step_j, delta_j, mut next_j) = axis(1, j);
+
+        loop {
+            if let Some(t) = self.intersec_cell(ray, i, j) {
+                if t > 0.0 {
+                    return Intersec::One(t);
+                }
+            }
+            if next_i < next_j {
+                if next_i > t_leave {
+                    break;
+                }
+                match i.checked_add_signed(step_i) {
+                    Some(c) if c < self.columns - 1 => i = c,
+                    _ => break,
+                }
+                next_i += delta_i;
+            } else {
+                if next_j > t_leave {
+                    break;
+                }
+                match j.checked_add_signed(step_j) {
+                    Some(c) if c < self.rows - 1 => j = c,
+                    _ => break,
+                }
+                next_j += delta_j;
+            }
+        }
+        Intersec::None
+    }
+
+    /// Normals of the samples interpolated bilinearly over the cell
+    fn norm(&self, point: &Vector) -> Vector {
+        let [x, _, z] = point.to_arr();
+        let ([i, j], [fx, fz]) = self.cell_at(x, z);
+        let n = |i: usize, j: usize| &self.normals[j * self.columns + i];
+        let mut res = (1.0 - fx) * (1.0 - fz) * n(i, j);
+        res += &(fx * (1.0 - fz) * n(i + 1, j));
+        res += &((1.0 - fx) * fz * n(i, j + 1));
+        res += &(fx * fz * n(i + 1, j + 1));
+        res.norm()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb {
+            min: self.min,
+            max: self.max,
+        })
+    }
+}
@@ -0,0 +1,445 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::image::Color;
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::triangle::{face_norm, intersec_triangle};
+use crate::raytracer::scene::shape::{Aabb, Bvh, Hit, Intersec, Shape, HIT_TOLERANCE};
+use crate::raytracer::vector::Vector;
+
+mod subdivision;
+
+pub use subdivision::Scheme;
+use subdivision::{edge, Cage, Edge};
+
+/// Raw geometry of the mesh as it's read from a file.
+#[derive(Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vector>,
+    /// Normals of the vertices, empty if the file has no normals
+    pub normals: Vec<Vector>,
+    /// Colors of the vertices, empty if the file has no colors
+    pub colors: Vec<Color>,
+    /// Triangles as indices of the vertices
+    pub faces: Vec<[usize; 3]>,
+    /// Polygons of the file before the triangulation, empty if the faces are the polygons
+    pub polygons: Vec<Vec<usize>>,
+}
+
+impl MeshData {
+    /// Scale the mesh uniformly relative to the origin of coordinates
+    pub fn scale(&mut self, k: f32) {
+        for v in self.vertices.iter_mut() {
+            *v = k * &*v;
+        }
+    }
+
+    /// Replace normals of the vertices by the average of normals of the faces around them
+    /// weighted by the faces area.
+    ///
+    /// Faces meeting at the angle bigger than the crease angle (in degrees) aren't smoothed
+    /// together, so their common vertices are split into several ones with different normals.
+    pub fn smooth_normals(&mut self, crease_angle: Option<f32>) {
+        // normals with length equal to the double area of the faces
+        let face_normals: Vec<Vector> = self
+            .faces
+            .iter()
+            .map(|f| {
+                let (a, b, c) = (
+                    &self.vertices[f[0]],
+                    &self.vertices[f[1]],
+                    &self.vertices[f[2]],
+                );
+                (b - a).cross(&(c - a))
+            })
+            .collect();
+        let mut vertex_faces = vec![Vec::new(); self.vertices.len()];
+        for (i, f) in self.faces.iter().enumerate() {
+            for v in f.iter() {
+                vertex_faces[*v].push(i);
+            }
+        }
+        let min_cos = crease_angle.map_or(-1.0, |a| a.to_radians().cos());
+        let is_smooth = |f: usize, g: usize| {
+            let (nf, ng) = (&face_normals[f], &face_normals[g]);
+            nf.dot(ng) >= min_cos * (nf.dot2() * ng.dot2()).sqrt()
+        };
+
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+        let mut colors = Vec::new();
+        // vertices of the new mesh by the old vertex and the faces smoothed with the corner
+        let mut split: HashMap<(usize, Vec<usize>), usize> = HashMap::new();
+        let mut faces = Vec::with_capacity(self.faces.len());
+        for (i, f) in self.faces.iter().enumerate() {
+            let face = f.map(|v| {
+                let group: Vec<usize> = vertex_faces[v]
+                    .iter()
+                    .copied()
+                    .filter(|g| is_smooth(i, *g))
+                    .collect();
+                *split.entry((v, group)).or_insert_with_key(|(_, group)| {
+                    let mut n = Vector::new(0.0, 0.0, 0.0);
+                    for g in group.iter() {
+                        n += &face_normals[*g];
+                    }
+                    vertices.push(self.vertices[v].clone());
+                    normals.push(n);
+                    if let Some(c) = self.colors.get(v) {
+                        colors.push(c.clone());
+                    }
+                    vertices.len() - 1
+                })
+            });
+            faces.push(face);
+        }
+
+        self.vertices = vertices;
+        self.normals = normals;
+        self.colors = colors;
+        self.faces = faces;
+        // split vertices don't fit the polygons any more
+        self.polygons = Vec::new();
+    }
+}
+
+impl MeshData {
+    /// Refine the mesh by the subdivision scheme levels times. Normals of the vertices are
+    /// dropped, since they don't fit the new surface. Catmull-Clark scheme refines the
+    /// polygons of the file, Loop scheme refines the triangles.
+    ///
+    /// Edges on the boundary and edges between faces meeting at the angle bigger than the
+    /// crease angle (in degrees) stay sharp.
+    pub fn subdivide(&mut self, scheme: Scheme, levels: usize, crease_angle: Option<f32>) {
+        self.weld();
+        // faces collapsed by the welding have no area and no edges to subdivide
+        self.faces
+            .retain(|f| f[0] != f[1] && f[1] != f[2] && f[2] != f[0]);
+        let polygons = match scheme {
+            Scheme::CatmullClark if !self.polygons.is_empty() => self.polygons.clone(),
+            _ => self.faces.iter().map(|f| f.to_vec()).collect(),
+        };
+        let mut cage = Cage {
+            faces: polygons.into_iter().filter_map(collapse).collect(),
+            sharp: self.creases(crease_angle),
+        };
+        let mut colors: Vec<Vector> = self
+            .colors
+            .iter()
+            .map(|c| Vector::new(c.r as f32, c.g as f32, c.b as f32))
+            .collect();
+
+        for _ in 0..levels {
+            let (stencils, next) = subdivision::subdivide(&cage, self.vertices.len(), scheme);
+            self.vertices = stencils.iter().map(|s| s.apply(&self.vertices)).collect();
+            if !colors.is_empty() {
+                colors = stencils.iter().map(|s| s.apply(&colors)).collect();
+            }
+            cage = next;
+        }
+
+        self.faces = cage
+            .faces
+            .iter()
+            .flat_map(|f| (1..f.len() - 1).map(|k| [f[0], f[k], f[k + 1]]))
+            .collect();
+        self.polygons = cage.faces;
+        self.normals = Vec::new();
+        self.colors = colors
+            .iter()
+            .map(|c| {
+                let [r, g, b] = c
+                    .to_arr()
+                    .map(|x| x.round().clamp(0.0, u8::MAX as f32) as u8);
+                Color::new(r, g, b)
+            })
+            .collect();
+    }
+
+    /// Merge vertices with the same position, files may repeat them with different normals
+    fn weld(&mut self) {
+        let mut index = HashMap::new();
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        let mut vertices = Vec::new();
+        let mut colors = Vec::new();
+        for (i, v) in self.vertices.iter().enumerate() {
+            let key = v.to_arr().map(f32::to_bits);
+            remap.push(*index.entry(key).or_insert_with(|| {
+                vertices.push(v.clone());
+                if let Some(c) = self.colors.get(i) {
+                    colors.push(c.clone());
+                }
+                vertices.len() - 1
+            }));
+        }
+        self.vertices = vertices;
+        self.colors = colors;
+        for f in self.faces.iter_mut() {
+            *f = f.map(|v| remap[v]);
+        }
+        for p in self.polygons.iter_mut() {
+            for v in p.iter_mut() {
+                *v = remap[*v];
+            }
+        }
+    }
+
+    /// Return edges between faces meeting at the angle bigger than the crease angle
+    fn creases(&self, crease_angle: Option<f32>) -> HashSet<Edge> {
+        let min_cos = match crease_angle {
+            None => return HashSet::new(),
+            Some(a) => a.to_radians().cos(),
+        };
+        let mut edge_faces: HashMap<Edge, Vec<Vector>> = HashMap::new();
+        for f in self.faces.iter() {
+            let n = face_norm(
+                &self.vertices[f[0]],
+                &self.vertices[f[1]],
+                &self.vertices[f[2]],
+            );
+            for k in 0..3 {
+                edge_faces
+                    .entry(edge(f[k], f[(k + 1) % 3]))
+                    .or_default()
+                    .push(n.clone());
+            }
+        }
+        edge_faces
+            .into_iter()
+            .filter(|(_, n)| n.len() == 2 && n[0].dot(&n[1]) < min_cos)
+            .map(|(e, _)| e)
+            .collect()
+    }
+}
+
+/// Remove repeated vertices going one after another around the polygon, None if less than
+/// three vertices are left
+fn collapse(mut polygon: Vec<usize>) -> Option<Vec<usize>> {
+    polygon.dedup();
+    while polygon.len() > 1 && polygon.first() == polygon.last() {
+        polygon.pop();
+    }
+    (polygon.len() >= 3).then_some(polygon)
+}
+
+/// Shape made of triangles sharing the vertices.
+///
+/// If the vertices have normals or colors they are interpolated over the faces. Faces are
+/// searched through the mesh's own bounding volume hierarchy, so instances of the mesh moved
+/// by transforms share it too.
+pub struct Mesh {
+    vertices: Vec<Vector>,
+    normals: Vec<Vector>,
+    colors: Vec<Color>,
+    faces: Vec<[usize; 3]>,
+    face_normals: Vec<Vector>,
+    bvh: Bvh,
+}
+
+/// Face of the mesh hit by a ray
+struct FaceHit {
+    face: usize,
+    distance: f32,
+    /// barycentric coordinates of the hit point
+    bary: [f32; 3],
+}
+
+impl Mesh {
+    pub fn new(data: MeshData) -> Mesh {
+        let vertices = data.vertices;
+        let face_normals = data
+            .faces
+            .iter()
+            .map(|f| face_norm(&vertices[f[0]], &vertices[f[1]], &vertices[f[2]]))
+            .collect();
+        let bounds: Vec<Aabb> = data
+            .faces
+            .iter()
+            .map(|f| Aabb::from_points(f.iter().map(|v| &vertices[*v])))
+            .collect();
+        let len = vertices.len();
+        Mesh {
+            bvh: Bvh::new(&bounds),
+            vertices,
+            normals: if data.normals.len() == len {
+                data.normals.into_iter().map(Vector::norm).collect()
+            } else {
+                Vec::new()
+            },
+            colors: if data.colors.len() == len {
+                data.colors
+            } else {
+                Vec::new()
+            },
+            faces: data.faces,
+            face_normals,
+        }
+    }
+
+    fn intersec_face(&self, ray: &Ray, face: usize) -> Option<(f32, f32, f32)> {
+        let f = &self.faces[face];
+        intersec_triangle(
+            ray,
+            &self.vertices[f[0]],
+            &self.vertices[f[1]],
+            &self.vertices[f[2]],
+        )
+    }
+
+    /// Return the nearest face hit by the ray closer than t_max
+    fn nearest_face(&self, ray: &Ray, t_max: f32) -> Option<FaceHit> {
+        let (face, t, (u, v)) = self.bvh.closest_with(ray, t_max, |i| {
+            self.intersec_face(ray, i).map(|(t, u, v)| (t, (u, v)))
+        })?;
+        Some(FaceHit {
+            face,
+            distance: t,
+            bary: [1.0 - u - v, u, v],
+        })
+    }
+
+    /// Return normal inside the face interpolated by normals of its vertices, None if
+    /// vertices have no normals
+    fn shading_norm(&self, hit: &FaceHit) -> Option<Vector> {
+        if self.normals.is_empty() {
+            return None;
+        }
+        let f = &self.faces[hit.face];
+        let mut n = Vector::new(0.0, 0.0, 0.0);
+        for (w, v) in hit.bary.iter().zip(f.iter()) {
+            n += &(*w * &self.normals[*v]);
+        }
+        Some(n.norm())
+    }
+
+    /// Return color inside the face interpolated by colors of its vertices
+    fn color(&self, hit: &FaceHit) -> Option<Color> {
+        if self.colors.is_empty() {
+            return None;
+        }
+        let f = &self.faces[hit.face];
+        let mix = |channel: fn(&Color) -> u8| {
+            let c: f32 = (0..3)
+                .map(|k| hit.bary[k] * channel(&self.colors[f[k]]) as f32)
+                .sum();
+            c.round().clamp(0.0, u8::MAX as f32) as u8
+        };
+        Some(Color::new(mix(|c| c.r), mix(|c| c.g), mix(|c| c.b)))
+    }
+}
+
+impl Shape for Mesh {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        match self.nearest_face(ray, f32::INFINITY) {
+            None => Intersec::None,
+            Some(hit) => Intersec::One(hit.distance),
+        }
+    }
+
+    /// Return normal of the face nearest to the point.
+    ///
+    /// It looks through all the faces, so hit is used for found by ray points.
+    fn norm(&self, point: &Vector) -> Vector {
+        let mut nearest = (0, f32::MAX);
+        for (i, f) in self.faces.iter().enumerate() {
+            let (a, b, c) = (
+                &self.vertices[f[0]],
+                &self.vertices[f[1]],
+                &self.vertices[f[2]],
+            );
+            let dist = (point - a).dot(&self.face_normals[i]).abs();
+            if dist < nearest.1 && is_inside(point, a, b, c) {
+                nearest = (i, dist);
+            }
+        }
+        self.face_normals[nearest.0].clone()
+    }
+
+    /// The face is searched only up to the distance, the hit is known to be there
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        let point = ray.point_on_ray(distance);
+        let t_max = distance + HIT_TOLERANCE * distance.abs().max(1.0);
+        let hit = match self.nearest_face(ray, t_max) {
+            Some(hit) => hit,
+            // the distance isn't of this mesh, the point is lit as if it faces the ray
+            None => {
+                return Hit {
+                    norm: -1.0 * ray.get_dir(),
+                    shading_norm: None,
+                    color: None,
+                    uv: None,
+                    point,
+                }
+            }
+        };
+
+        // the face is lit from both sides
+        let sign = if self.face_normals[hit.face].dot(ray.get_dir()) > 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        Hit {
+            point,
+            norm: sign * &self.face_normals[hit.face],
+            shading_norm: self.shading_norm(&hit).map(|n| sign * &n),
+            color: self.color(&hit),
+            uv: None,
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.bvh.bounds().cloned()
+    }
+}
+
+/// Check if projection of the point on the triangle plane lies inside the triangle
+fn is_inside(point: &Vector, a: &Vector, b: &Vector, c: &Vector) -> bool {
+    let n = (b - a).cross(&(c - a));
+    [(a, b), (b, c), (c, a)]
+        .iter()
+        .all(|(from, to)| (*to - *from).cross(&(point - from)).dot(&n) >= 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::scene::shape::{MeshData, Scheme};
+    use crate::raytracer::vector::Vector;
+
+    #[test]
+    fn quad_cube_goes_to_catmull_clark_limit() {
+        let mut data = MeshData::default();
+        for k in 0..8 {
+            let c = |bit: usize| if k & bit == 0 { -1.0 } else { 1.0 };
+            data.vertices.push(Vector::new(c(1), c(2), c(4)));
+        }
+        data.polygons = vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+        ];
+        data.faces = data
+            .polygons
+            .iter()
+            .flat_map(|p| [[p[0], p[1], p[2]], [p[0], p[2], p[3]]])
+            .collect();
+        data.subdivide(Scheme::CatmullClark, 5, None);
+
+        // limit points of the corner (valence 3) and of the center of the face (valence 4)
+        // of the cube from -1 to 1 are at 1/2 and 68/81
+        let corner = &data.vertices[7];
+        assert!((corner - &Vector::new(0.5, 0.5, 0.5)).size() < 1e-3);
+        let center: Vec<[f32; 3]> = data
+            .vertices
+            .iter()
+            .map(|p| p.to_arr())
+            .filter(|[x, y, z]| *x > 0.0 && y.abs() < 1e-6 && z.abs() < 1e-6)
+            .collect();
+        assert_eq!(center.len(), 1);
+        assert!((center[0][0] - 68.0 / 81.0).abs() < 1e-3);
+        // quads are split into two triangles only after the subdivision
+        assert_eq!(data.faces.len(), 6 * 4usize.pow(5) * 2);
+    }
+}
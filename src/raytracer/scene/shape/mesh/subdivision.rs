@@ -0,0 +1,343 @@
+//! Subdivision surfaces: Loop scheme for triangles and Catmull-Clark scheme for any
+//! polygons.
+//!
+//! Sharp edges (on the boundary of the mesh or marked as creases) are subdivided as cubic
+//! B-spline curves, so they stay sharp and the boundary stays on the lines of its straight
+//! edges, the vertex between two sharp edges is smoothed along them like a point of the
+//! curve. Vertices of the boundary with only two edges and vertices with more than two sharp
+//! edges are corners and don't move, so corners of the boundary are kept.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::raytracer::vector::Vector;
+
+/// Edge given by its vertices in ascending order
+pub type Edge = (usize, usize);
+
+#[derive(Clone, Copy)]
+pub enum Scheme {
+    Loop,
+    CatmullClark,
+}
+
+/// Mesh made of polygons with the sharp edges, vertices are the same as in MeshData
+pub struct Cage {
+    pub faces: Vec<Vec<usize>>,
+    pub sharp: HashSet<Edge>,
+}
+
+/// New vertex as the weighted sum of the old ones
+#[derive(Clone, Default)]
+pub struct Stencil(Vec<(usize, f32)>);
+
+impl Stencil {
+    fn vertex(v: usize) -> Stencil {
+        Stencil(vec![(v, 1.0)])
+    }
+
+    fn add(&mut self, v: usize, w: f32) {
+        self.0.push((v, w));
+    }
+
+    fn add_stencil(&mut self, other: &Stencil, w: f32) {
+        self.0.extend(other.0.iter().map(|(v, x)| (*v, x * w)));
+    }
+
+    /// Return the weighted sum of the old vertices
+    pub fn apply(&self, points: &[Vector]) -> Vector {
+        let mut p = Vector::new(0.0, 0.0, 0.0);
+        for (v, w) in self.0.iter() {
+            p += &(*w * &points[*v]);
+        }
+        p
+    }
+}
+
+pub fn edge(a: usize, b: usize) -> Edge {
+    (usize::min(a, b), usize::max(a, b))
+}
+
+/// Edges of the cage with the faces around them and the neighbours of the vertices
+struct Topology {
+    edges: HashMap<Edge, Vec<usize>>,
+    neighbours: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(cage: &Cage, vertices_len: usize) -> Topology {
+        let mut edges: HashMap<Edge, Vec<usize>> = HashMap::new();
+        let mut neighbours = vec![Vec::new(); vertices_len];
+        let mut vertex_faces = vec![Vec::new(); vertices_len];
+        for (i, f) in cage.faces.iter().enumerate() {
+            for (k, &a) in f.iter().enumerate() {
+                let b = f[(k + 1) % f.len()];
+                let faces = edges.entry(edge(a, b)).or_default();
+                if faces.is_empty() {
+                    neighbours[a].push(b);
+                    neighbours[b].push(a);
+                }
+                // degenerate faces like [a, a, b] go along the same edge twice
+                if faces.last() != Some(&i) {
+                    faces.push(i);
+                }
+                if vertex_faces[a].last() != Some(&i) {
+                    vertex_faces[a].push(i);
+                }
+            }
+        }
+        Topology {
+            edges,
+            neighbours,
+            vertex_faces,
+        }
+    }
+
+    /// Sharp edges have not two faces around them or are marked as creases
+    fn is_sharp(&self, cage: &Cage, e: Edge) -> bool {
+        self.edges[&e].len() != 2 || cage.sharp.contains(&e)
+    }
+
+    /// Return the stencil of the vertex moved by the rules of sharp edges, None if the
+    /// vertex is smooth. The vertex with two edges only belongs to a single face, it's a
+    /// corner of the boundary.
+    fn sharp_vertex(&self, cage: &Cage, v: usize) -> Option<Stencil> {
+        let sharp: Vec<usize> = self.neighbours[v]
+            .iter()
+            .copied()
+            .filter(|n| self.is_sharp(cage, edge(v, *n)))
+            .collect();
+        match sharp[..] {
+            [a, b] if self.neighbours[v].len() > 2 => {
+                let mut s = Stencil::default();
+                s.add(v, 0.75);
+                s.add(a, 0.125);
+                s.add(b, 0.125);
+                Some(s)
+            }
+            [] | [_] => None,
+            _ => Some(Stencil::vertex(v)),
+        }
+    }
+}
+
+/// Subdivide the cage once, return the stencils of the new vertices and the new cage
+pub fn subdivide(cage: &Cage, vertices_len: usize, scheme: Scheme) -> (Vec<Stencil>, Cage) {
+    let topology = Topology::new(cage, vertices_len);
+    match scheme {
+        Scheme::Loop => subdivide_loop(cage, &topology, vertices_len),
+        Scheme::CatmullClark => subdivide_catmull_clark(cage, &topology, vertices_len),
+    }
+}
+
+/// Give every edge its new vertex going after the old ones, return the new vertices of
+/// the edges and the sharp edges of the new cage
+fn split_edges(
+    cage: &Cage,
+    topology: &Topology,
+    first: usize,
+) -> (HashMap<Edge, usize>, HashSet<Edge>) {
+    let mut sorted: Vec<Edge> = topology.edges.keys().copied().collect();
+    sorted.sort_unstable();
+    let mids: HashMap<Edge, usize> = sorted
+        .into_iter()
+        .enumerate()
+        .map(|(i, e)| (e, first + i))
+        .collect();
+    let sharp = cage
+        .sharp
+        .iter()
+        .filter_map(|e| mids.get(e).map(|m| (e, *m)))
+        .flat_map(|(&(a, b), m)| [edge(a, m), edge(m, b)])
+        .collect();
+    (mids, sharp)
+}
+
+fn subdivide_loop(cage: &Cage, topology: &Topology, vertices_len: usize) -> (Vec<Stencil>, Cage) {
+    let mut stencils: Vec<Stencil> = (0..vertices_len)
+        .map(|v| {
+            if let Some(s) = topology.sharp_vertex(cage, v) {
+                return s;
+            }
+            let n = topology.neighbours[v].len();
+            let beta = if n == 3 {
+                3.0 / 16.0
+            } else {
+                3.0 / (8.0 * n as f32)
+            };
+            let mut s = Stencil::default();
+            s.add(v, 1.0 - n as f32 * beta);
+            for u in topology.neighbours[v].iter() {
+                s.add(*u, beta);
+            }
+            s
+        })
+        .collect();
+
+    let (mids, sharp) = split_edges(cage, topology, vertices_len);
+    let mut edge_stencils = vec![Stencil::default(); mids.len()];
+    for (&(a, b), faces) in topology.edges.iter() {
+        let s = &mut edge_stencils[mids[&(a, b)] - vertices_len];
+        // degenerate faces have no vertex opposite to the edge, then it's sharp
+        let opposites: Option<Vec<usize>> = faces
+            .iter()
+            .map(|f| cage.faces[*f].iter().copied().find(|v| *v != a && *v != b))
+            .collect();
+        let opposites = match opposites {
+            Some(o) if !topology.is_sharp(cage, (a, b)) => o,
+            _ => {
+                s.add(a, 0.5);
+                s.add(b, 0.5);
+                continue;
+            }
+        };
+        s.add(a, 0.375);
+        s.add(b, 0.375);
+        for v in opposites {
+            s.add(v, 0.125);
+        }
+    }
+    stencils.extend(edge_stencils);
+
+    let mut faces = Vec::with_capacity(cage.faces.len() * 4);
+    for f in cage.faces.iter() {
+        let (a, b, c) = (f[0], f[1], f[2]);
+        let (ab, bc, ca) = (mids[&edge(a, b)], mids[&edge(b, c)], mids[&edge(c, a)]);
+        faces.push(vec![a, ab, ca]);
+        faces.push(vec![ab, b, bc]);
+        faces.push(vec![ca, bc, c]);
+        faces.push(vec![ab, bc, ca]);
+    }
+    (stencils, Cage { faces, sharp })
+}
+
+fn subdivide_catmull_clark(
+    cage: &Cage,
+    topology: &Topology,
+    vertices_len: usize,
+) -> (Vec<Stencil>, Cage) {
+    // face points are the centroids of the faces
+    let face_points: Vec<Stencil> = cage
+        .faces
+        .iter()
+        .map(|f| {
+            let mut s = Stencil::default();
+            for v in f.iter() {
+                s.add(*v, 1.0 / f.len() as f32);
+            }
+            s
+        })
+        .collect();
+
+    // vertex point is (F + 2 R + (n - 3) v) / n, where F is the average of the face points
+    // around it and R is the average of the midpoints of its edges
+    let mut stencils: Vec<Stencil> = (0..vertices_len)
+        .map(|v| {
+            if let Some(s) = topology.sharp_vertex(cage, v) {
+                return s;
+            }
+            let n = topology.neighbours[v].len() as f32;
+            let faces = &topology.vertex_faces[v];
+            let mut s = Stencil::default();
+            s.add(v, (n - 3.0) / n + 1.0 / n);
+            for f in faces.iter() {
+                s.add_stencil(&face_points[*f], 1.0 / (n * faces.len() as f32));
+            }
+            for u in topology.neighbours[v].iter() {
+                s.add(*u, 1.0 / (n * n));
+            }
+            s
+        })
+        .collect();
+
+    let (mids, sharp) = split_edges(cage, topology, vertices_len);
+    let mut edge_stencils = vec![Stencil::default(); mids.len()];
+    for (&(a, b), faces) in topology.edges.iter() {
+        let s = &mut edge_stencils[mids[&(a, b)] - vertices_len];
+        if topology.is_sharp(cage, (a, b)) {
+            s.add(a, 0.5);
+            s.add(b, 0.5);
+            continue;
+        }
+        s.add(a, 0.25);
+        s.add(b, 0.25);
+        for f in faces.iter() {
+            s.add_stencil(&face_points[*f], 0.25);
+        }
+    }
+    stencils.extend(edge_stencils);
+
+    let first_face_point = stencils.len();
+    stencils.extend(face_points);
+
+    let mut faces = Vec::new();
+    for (i, f) in cage.faces.iter().enumerate() {
+        for (k, &v) in f.iter().enumerate() {
+            let next = f[(k + 1) % f.len()];
+            let prev = f[(k + f.len() - 1) % f.len()];
+            faces.push(vec![
+                v,
+                mids[&edge(v, next)],
+                first_face_point + i,
+                mids[&edge(prev, v)],
+            ]);
+        }
+    }
+    (stencils, Cage { faces, sharp })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::raytracer::scene::shape::mesh::subdivision::{subdivide, Cage, Scheme};
+    use crate::raytracer::vector::Vector;
+
+    #[test]
+    fn boundary_edges_stay_on_their_lines_and_corners_stay_fixed() {
+        // flat square of two triangles, all its edges are on the boundary
+        let points = [
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        ];
+        let cage = Cage {
+            faces: vec![vec![0, 1, 2], vec![0, 2, 3]],
+            sharp: HashSet::new(),
+        };
+        for scheme in [Scheme::Loop, Scheme::CatmullClark] {
+            let (stencils, _) = subdivide(&cage, points.len(), scheme);
+            let new: Vec<Vector> = stencils.iter().map(|s| s.apply(&points)).collect();
+            // corner of a single triangle doesn't move
+            assert_eq!(new[1], points[1]);
+            // corner at the diagonal is smoothed along the boundary like a point of the curve
+            assert_eq!(new[0], Vector::new(0.125, 0.125, 0.0));
+            // midpoint of the boundary edge
+            assert!(new.contains(&Vector::new(0.5, 0.0, 0.0)));
+            assert!(new.iter().all(|p| p.to_arr()[2] == 0.0));
+        }
+    }
+
+    #[test]
+    fn degenerate_faces_are_subdivided() {
+        let points = [
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(2.0, 2.0, 0.0),
+        ];
+        // degenerate faces go along the edges (1, 2) and (2, 4) twice and have no vertex
+        // opposite to them
+        let cage = Cage {
+            faces: vec![vec![0, 1, 2], vec![0, 2, 3], vec![1, 1, 2], vec![2, 4, 4]],
+            sharp: HashSet::new(),
+        };
+        for scheme in [Scheme::Loop, Scheme::CatmullClark] {
+            let (stencils, _) = subdivide(&cage, points.len(), scheme);
+            let new: Vec<Vector> = stencils.iter().map(|s| s.apply(&points)).collect();
+            assert!(new.iter().all(|p| p.to_arr().iter().all(|c| c.is_finite())));
+        }
+    }
+}
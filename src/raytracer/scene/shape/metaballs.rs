@@ -0,0 +1,189 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{poly, Aabb, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Center of the field of the metaballs, the field is weight * (1 - d^2 / radius^2)^3 at the
+/// distance d < radius from the center and zero farther. Negative weight carves the others.
+pub struct Ball {
+    pub center: Vector,
+    pub radius: f32,
+    pub weight: f32,
+}
+
+/// Blobby surface where the sum of the fields of the balls is equal to the threshold, it's
+/// solid inside where the sum is bigger.
+pub struct Metaballs {
+    balls: Vec<Ball>,
+    threshold: f32,
+}
+
+pub fn new_metaballs(balls: Vec<Ball>, threshold: f32) -> impl Shape {
+    Metaballs {
+        balls: balls
+            .into_iter()
+            .map(|b| Ball {
+                radius: b.radius.abs(),
+                ..b
+            })
+            .collect(),
+        threshold,
+    }
+}
+
+impl Metaballs {
+    fn field(&self, p: &Vector) -> f32 {
+        self.balls
+            .iter()
+            .map(|b| {
+                let k = 1.0 - (p - &b.center).dot2() / (b.radius * b.radius);
+                if k > 0.0 {
+                    b.weight * k * k * k
+                } else {
+                    0.0
+                }
+            })
+            .sum()
+    }
+
+    /// Return distances where the field along the ray is equal to the threshold.
+    ///
+    /// The ray is cut into segments by the spheres of influence of the balls, the set of
+    /// the balls is the same along the segment, so the field is a polynomial of the 6th
+    /// degree there and its roots are found exactly.
+    fn roots(&self, ray: &Ray) -> Vec<f32> {
+        let dir = ray.get_dir();
+        // spheres of influence along the ray
+        let spans: Vec<(&Ball, f32, f32)> = self
+            .balls
+            .iter()
+            .filter_map(|b| {
+                let oc = &b.center - ray.get_orig();
+                let oc_dir = oc.dot(dir);
+                let k = b.radius * b.radius - oc.cross(dir).dot2();
+                (k > 0.0).then(|| (b, oc_dir - k.sqrt(), oc_dir + k.sqrt()))
+            })
+            .collect();
+        let mut borders: Vec<f32> = spans.iter().flat_map(|&(_, t1, t2)| [t1, t2]).collect();
+        borders.sort_by(f32::total_cmp);
+
+        let mut roots = Vec::new();
+        for seg in borders.windows(2) {
+            let (start, len) = (seg[0], (seg[1] - seg[0]) as f64);
+            if len <= 0.0 {
+                continue;
+            }
+            // the segment starts at the origin of the parameter, so coefficients stay small
+            let p = ray.point_on_ray(start);
+            let mid = 0.5 * (seg[0] + seg[1]);
+            let mut coeffs = vec![0.0; 7];
+            coeffs[6] = -self.threshold as f64;
+            for (b, _, _) in spans.iter().filter(|(_, t1, t2)| *t1 < mid && mid < *t2) {
+                // 1 - |p - c + s d|^2 / r^2 = a s^2 + b s + c
+                let pc = &p - &b.center;
+                let r2 = (b.radius * b.radius) as f64;
+                let q = [
+                    -dir.dot2() as f64 / r2,
+                    -2.0 * pc.dot(dir) as f64 / r2,
+                    1.0 - pc.dot2() as f64 / r2,
+                ];
+                let cube = poly::mul(&poly::mul(&q, &q), &q);
+                for (c, x) in coeffs.iter_mut().zip(cube) {
+                    *c += b.weight as f64 * x;
+                }
+            }
+            roots.extend(
+                poly::solve(&coeffs)
+                    .into_iter()
+                    .filter(|s| (0.0..=len).contains(s))
+                    .map(|s| start + s as f32),
+            );
+        }
+        roots
+    }
+}
+
+impl Shape for Metaballs {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let roots = self.roots(ray);
+
+        // a root may touch the surface without crossing it, so the field is checked
+        // between the roots
+        let mut intervals: Vec<(f32, f32)> = Vec::new();
+        for w in roots.windows(2) {
+            let mid = ray.point_on_ray(0.5 * (w[0] + w[1]));
+            if self.field(&mid) <= self.threshold {
+                continue;
+            }
+            match intervals.last_mut() {
+                Some(last) if last.1 == w[0] => last.1 = w[1],
+                _ => intervals.push((w[0], w[1])),
+            }
+        }
+        Intersec::from_intervals(intervals)
+    }
+
+    /// Normal is opposite to the gradient of the field
+    fn norm(&self, point: &Vector) -> Vector {
+        let mut n = Vector::new(0.0, 0.0, 0.0);
+        for b in self.balls.iter() {
+            let pc = point - &b.center;
+            let r2 = b.radius * b.radius;
+            let k = 1.0 - pc.dot2() / r2;
+            if k > 0.0 {
+                n += &((6.0 * b.weight * k * k / r2) * &pc);
+            }
+        }
+        n.norm()
+    }
+
+    /// The surface is inside the influence spheres of the balls adding to the field, unless
+    /// the threshold is negative and all the space outside is solid too
+    fn bounds(&self) -> Option<Aabb> {
+        if self.threshold <= 0.0 {
+            return None;
+        }
+        Some(
+            self.balls
+                .iter()
+                .filter(|b| b.weight > 0.0)
+                .fold(Aabb::empty(), |bounds, b| {
+                    bounds.union(&Aabb::around(&b.center, b.radius))
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::ray::Ray;
+    use crate::raytracer::scene::shape::metaballs::{Ball, Metaballs};
+    use crate::raytracer::vector::Vector;
+
+    #[test]
+    fn isolated_ball_is_sphere() {
+        // the first ball is missed by the ray
+        let m = Metaballs {
+            balls: vec![
+                Ball {
+                    center: Vector::new(0.0, 10.0, 0.0),
+                    radius: 1.0,
+                    weight: 1.0,
+                },
+                Ball {
+                    center: Vector::new(0.0, 0.0, 0.0),
+                    radius: 2.0,
+                    weight: 1.0,
+                },
+            ],
+            threshold: 0.125,
+        };
+        let ray = Ray::new(Vector::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+
+        // (1 - d^2 / 4)^3 = 1 / 8 at d = sqrt(2)
+        let d = 2.0f32.sqrt();
+        let roots = m.roots(&ray);
+        assert_eq!(roots.len(), 2);
+        assert!((roots[0] - (5.0 - d)).abs() < 1e-4);
+        assert!((roots[1] - (5.0 + d)).abs() < 1e-4);
+    }
+}
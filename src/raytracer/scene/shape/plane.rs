@@ -0,0 +1,37 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Infinite plane given by any point on it and its normal.
+///
+/// Plane is one sided: it is lit only from the side its normal looks to.
+pub struct Plane {
+    point: Vector,
+    normal: Vector,
+}
+
+pub fn new_plane(point: Vector, normal: Vector) -> impl Shape {
+    Plane {
+        point,
+        normal: normal.norm(),
+    }
+}
+
+impl Shape for Plane {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let denom = self.normal.dot(ray.get_dir());
+        if denom.abs() < f32::EPSILON {
+            return Intersec::None;
+        }
+
+        let t = (&self.point - ray.get_orig()).dot(&self.normal) / denom;
+        if t <= 0.0 {
+            return Intersec::None;
+        }
+        Intersec::One(t)
+    }
+
+    fn norm(&self, _point: &Vector) -> Vector {
+        self.normal.clone()
+    }
+}
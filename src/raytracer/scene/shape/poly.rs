@@ -0,0 +1,148 @@
+//! Real roots of polynomials.
+//!
+//! Roots are isolated between the roots of the derivative, where the polynomial is monotonic,
+//! and then refined by Newton's method guarded by bisection. It's slower than the closed
+//! formulas, but doesn't lose roots by cancellation errors.
+
+/// Return real roots of the polynomial in ascending order, coefficients go from the highest
+/// degree to the constant
+pub fn solve(coeffs: &[f64]) -> Vec<f64> {
+    let max = coeffs.iter().fold(0.0, |m: f64, c| m.max(c.abs()));
+    if max == 0.0 {
+        return Vec::new();
+    }
+    // leading coefficients too small in comparison with others make roots go to infinity
+    let start = coeffs
+        .iter()
+        .position(|c| c.abs() > max * 1e-12)
+        .unwrap_or(coeffs.len());
+    let coeffs = &coeffs[start..];
+
+    match coeffs.len() {
+        0 | 1 => Vec::new(),
+        2 => vec![-coeffs[1] / coeffs[0]],
+        3 => solve_quadratic(coeffs[0], coeffs[1], coeffs[2]),
+        _ => solve_isolated(coeffs),
+    }
+}
+
+/// Return value of the polynomial at x
+pub fn eval(coeffs: &[f64], x: f64) -> f64 {
+    coeffs.iter().fold(0.0, |acc, c| acc * x + c)
+}
+
+/// Return coefficients of the derivative of the polynomial
+pub fn derivative(coeffs: &[f64]) -> Vec<f64> {
+    let n = coeffs.len() - 1;
+    coeffs[..n]
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c * (n - i) as f64)
+        .collect()
+}
+
+/// Return coefficients of the product of two polynomials
+pub fn mul(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut res = vec![0.0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            res[i + j] += x * y;
+        }
+    }
+    res
+}
+
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return Vec::new();
+    }
+    // avoid subtraction of close numbers
+    let q = -0.5 * (b + b.signum() * disc.sqrt());
+    if q == 0.0 {
+        return vec![0.0, 0.0];
+    }
+    let (x1, x2) = (q / a, c / q);
+    vec![x1.min(x2), x1.max(x2)]
+}
+
+fn solve_isolated(coeffs: &[f64]) -> Vec<f64> {
+    // Cauchy bound of the roots
+    let bound = 1.0
+        + coeffs[1..]
+            .iter()
+            .fold(0.0, |m: f64, c| m.max((c / coeffs[0]).abs()));
+
+    let deriv = derivative(coeffs);
+    let mut points = vec![-bound];
+    points.extend(solve(&deriv).into_iter().filter(|x| x.abs() < bound));
+    points.push(bound);
+
+    let mut roots = Vec::new();
+    for w in points.windows(2) {
+        let (lo, hi) = (w[0], w[1]);
+        let (f_lo, f_hi) = (eval(coeffs, lo), eval(coeffs, hi));
+        if f_lo == 0.0 {
+            roots.push(lo);
+        } else if f_lo.signum() != f_hi.signum() {
+            roots.push(refine(coeffs, &deriv, lo, hi, f_lo));
+        }
+    }
+    if eval(coeffs, bound) == 0.0 {
+        roots.push(bound);
+    }
+    roots.dedup();
+    roots
+}
+
+/// Find the root inside [lo, hi] where polynomial changes its sign
+fn refine(coeffs: &[f64], deriv: &[f64], mut lo: f64, mut hi: f64, f_lo: f64) -> f64 {
+    const MAX_ITER: usize = 100;
+
+    let mut x = 0.5 * (lo + hi);
+    for _ in 0..MAX_ITER {
+        let f = eval(coeffs, x);
+        if f == 0.0 {
+            return x;
+        }
+        if f.signum() == f_lo.signum() {
+            lo = x;
+        } else {
+            hi = x;
+        }
+
+        let newton = x - f / eval(deriv, x);
+        let next = if newton > lo && newton < hi {
+            newton
+        } else {
+            0.5 * (lo + hi)
+        };
+        if (next - x).abs() <= f64::EPSILON * (1.0 + x.abs()) {
+            return next;
+        }
+        x = next;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::scene::shape::poly::solve;
+
+    #[test]
+    fn quartic_with_close_roots() {
+        // (x - 1)(x - 1.001)(x + 2)(x - 40)
+        let coeffs = [1.0, -40.001, -2.961, 122.042, -80.08];
+        let roots = solve(&coeffs);
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([-2.0, 1.0, 1.001, 40.0]) {
+            assert!((root - expected).abs() < 1e-9, "{} != {}", root, expected);
+        }
+    }
+
+    #[test]
+    fn quartic_without_roots() {
+        // (x^2 + 1)(x^2 + 4)
+        assert!(solve(&[1.0, 0.0, 5.0, 0.0, 4.0]).is_empty());
+    }
+}
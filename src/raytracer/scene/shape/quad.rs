@@ -0,0 +1,81 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{face_forward, Aabb, Hit, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Flat parallelogram given by its corner and two edges going from the corner, it is lit
+/// from both sides.
+pub struct Quad {
+    corner: Vector,
+    edge_u: Vector,
+    edge_v: Vector,
+    normal: Vector,
+    /// edge_u cross edge_v, its size is the area of the quad
+    cross: Vector,
+}
+
+pub fn new_quad(corner: Vector, edge_u: Vector, edge_v: Vector) -> impl Shape {
+    let cross = edge_u.cross(&edge_v);
+    Quad {
+        normal: cross.clone().norm(),
+        cross,
+        corner,
+        edge_u,
+        edge_v,
+    }
+}
+
+impl Quad {
+    /// Return texture coordinates of the point, they are coordinates along the edges in
+    /// the edge sizes, so the point is corner + u * edge_u + v * edge_v
+    pub fn uv(&self, point: &Vector) -> (f32, f32) {
+        let p = point - &self.corner;
+        let area2 = self.cross.dot2();
+        let u = p.cross(&self.edge_v).dot(&self.cross) / area2;
+        let v = self.edge_u.cross(&p).dot(&self.cross) / area2;
+        (u, v)
+    }
+}
+
+impl Shape for Quad {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let denom = self.normal.dot(ray.get_dir());
+        if denom.abs() < f32::EPSILON {
+            return Intersec::None;
+        }
+
+        let t = (&self.corner - ray.get_orig()).dot(&self.normal) / denom;
+        if t <= 0.0 {
+            return Intersec::None;
+        }
+        let (u, v) = self.uv(&ray.point_on_ray(t));
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return Intersec::None;
+        }
+        Intersec::One(t)
+    }
+
+    fn norm(&self, _point: &Vector) -> Vector {
+        self.normal.clone()
+    }
+
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        let point = ray.point_on_ray(distance);
+        Hit {
+            norm: face_forward(self.normal.clone(), ray),
+            shading_norm: None,
+            color: None,
+            uv: Some(self.uv(&point)),
+            point,
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let opposite = &self.corner + &(&self.edge_u + &self.edge_v);
+        Some(Aabb::from_points([
+            &self.corner,
+            &(&self.corner + &self.edge_u),
+            &(&self.corner + &self.edge_v),
+            &opposite,
+        ]))
+    }
+}
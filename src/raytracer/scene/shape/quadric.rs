@@ -0,0 +1,245 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{face_forward, poly, Aabb, Hit, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Surface of the second degree (x - c)^T M (x - c) + L (x - c) + k = 0, it's solid
+/// inside where the left side is negative.
+///
+/// Clipped by the box quadric is an open surface (for example parabolic mirror) lit from
+/// both sides, only the part of the surface inside the box is kept.
+pub struct Quadric {
+    center: Vector,
+    /// symmetric matrix of the quadratic form by rows
+    m: [Vector; 3],
+    l: Vector,
+    k: f32,
+    bounds: Option<([f32; 3], [f32; 3])>,
+}
+
+/// Quadric given by 10 coefficients of
+/// a x^2 + b y^2 + c z^2 + d xy + e yz + f xz + g x + h y + i z + j = 0
+pub fn new_quadric(coeffs: [f32; 10]) -> Quadric {
+    let [a, b, c, d, e, f, g, h, i, j] = coeffs;
+    Quadric {
+        center: Vector::new(0.0, 0.0, 0.0),
+        m: [
+            Vector::new(a, d / 2.0, f / 2.0),
+            Vector::new(d / 2.0, b, e / 2.0),
+            Vector::new(f / 2.0, e / 2.0, c),
+        ],
+        l: Vector::new(g, h, i),
+        k: j,
+        bounds: None,
+    }
+}
+
+/// Ellipsoid with semi-axes along the coordinate axes
+pub fn new_ellipsoid(center: Vector, radii: [f32; 3]) -> Quadric {
+    let [a, b, c] = radii.map(|r| 1.0 / (r * r));
+    Quadric {
+        center,
+        ..new_quadric([a, b, c, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0])
+    }
+}
+
+/// Paraboloid of revolution opening along the axis, its focus is at the focal length
+/// from the vertex
+pub fn new_paraboloid(vertex: Vector, axis: Vector, focal_length: f32) -> Quadric {
+    let axis = axis.norm();
+    // |x|^2 - (x a)^2 - 4 f (x a) = 0
+    Quadric {
+        center: vertex,
+        m: outer(&axis, -1.0, 1.0),
+        l: (-4.0 * focal_length) * &axis,
+        k: 0.0,
+        bounds: None,
+    }
+}
+
+/// Hyperboloid of revolution around the axis, radius is the semi-axis across the axis (the
+/// radius of the waist of one sheet) and half_length is the semi-axis along the axis (the
+/// distance from the center to the vertices of two sheets)
+pub fn new_hyperboloid(
+    center: Vector,
+    axis: Vector,
+    radius: f32,
+    half_length: f32,
+    two_sheets: bool,
+) -> Quadric {
+    let axis = axis.norm();
+    let (r2, h2) = (radius * radius, half_length * half_length);
+    // |x across|^2 / r^2 - (x a)^2 / h^2 = 1 for one sheet and -1 for two sheets, so
+    // inside of the cups of two sheets is solid
+    let m = outer(&axis, -(1.0 / r2 + 1.0 / h2), 1.0 / r2);
+    Quadric {
+        center,
+        m,
+        l: Vector::new(0.0, 0.0, 0.0),
+        k: if two_sheets { 1.0 } else { -1.0 },
+        bounds: None,
+    }
+}
+
+/// Return rows of the matrix s * I + k * a a^T
+fn outer(a: &Vector, k: f32, s: f32) -> [Vector; 3] {
+    let a_arr = a.to_arr();
+    [0, 1, 2].map(|i| {
+        let mut row = (k * a_arr[i]) * a;
+        let mut diag = [0.0; 3];
+        diag[i] = s;
+        row += &Vector::new_from_arr(&diag);
+        row
+    })
+}
+
+impl Quadric {
+    /// Keep only the part of the surface inside the box
+    pub fn clip(mut self, min: Vector, max: Vector) -> Quadric {
+        let (min, max) = (min.to_arr(), max.to_arr());
+        self.bounds = Some((
+            [0, 1, 2].map(|i| f32::min(min[i], max[i])),
+            [0, 1, 2].map(|i| f32::max(min[i], max[i])),
+        ));
+        self
+    }
+
+    /// Return the box around the surface if it's an ellipsoid: M is positive definite and
+    /// the surface is (y - y0)^T M (y - y0) = r for y = x - center, y0 = -M^-1 L / 2 and
+    /// r = L^T M^-1 L / 4 - k > 0
+    fn ellipsoid_bounds(&self) -> Option<Aabb> {
+        let m = self.m.clone().map(|row| row.to_arr());
+        let minor = m[0][0] * m[1][1] - m[0][1] * m[1][0];
+        let det = self.m[0].dot(&self.m[1].cross(&self.m[2]));
+        if m[0][0] <= 0.0 || minor <= 0.0 || det <= 0.0 {
+            return None;
+        }
+        // inverse of the symmetric matrix by the cofactors
+        let inv = [
+            &self.m[1].cross(&self.m[2]) * (1.0 / det),
+            &self.m[2].cross(&self.m[0]) * (1.0 / det),
+            &self.m[0].cross(&self.m[1]) * (1.0 / det),
+        ];
+        let inv_l = Vector::new(
+            inv[0].dot(&self.l),
+            inv[1].dot(&self.l),
+            inv[2].dot(&self.l),
+        );
+        let r = 0.25 * self.l.dot(&inv_l) - self.k;
+        if r <= 0.0 {
+            return None;
+        }
+        let center = (&self.center - &(0.5 * &inv_l)).to_arr();
+        let extent = [0, 1, 2].map(|i| (r * inv[i].to_arr()[i]).sqrt());
+        Some(Aabb {
+            min: [0, 1, 2].map(|i| center[i] - extent[i]),
+            max: [0, 1, 2].map(|i| center[i] + extent[i]),
+        })
+    }
+
+    fn mul(&self, v: &Vector) -> Vector {
+        Vector::new(self.m[0].dot(v), self.m[1].dot(v), self.m[2].dot(v))
+    }
+
+    fn is_in_bounds(&self, p: &Vector) -> bool {
+        match &self.bounds {
+            None => true,
+            Some((min, max)) => {
+                let p = p.to_arr();
+                (0..3).all(|i| min[i] <= p[i] && p[i] <= max[i])
+            }
+        }
+    }
+}
+
+impl Shape for Quadric {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let p = ray.get_orig() - &self.center;
+        let d = ray.get_dir();
+        let md = self.mul(d);
+        let a = d.dot(&md) as f64;
+        let b = (2.0 * p.dot(&md) + self.l.dot(d)) as f64;
+        let c = (p.dot(&self.mul(&p)) + self.l.dot(&p) + self.k) as f64;
+        let roots: Vec<f32> = poly::solve(&[a, b, c])
+            .into_iter()
+            .map(|t| t as f32)
+            .collect();
+
+        if self.bounds.is_some() {
+            return match roots
+                .into_iter()
+                .find(|t| *t > 0.0 && self.is_in_bounds(&ray.point_on_ray(*t)))
+            {
+                Some(t) => Intersec::One(t),
+                None => Intersec::None,
+            };
+        }
+
+        // the quadric is inside where the polynomial a t^2 + b t + c is negative
+        let (inf, neg_inf) = (f32::INFINITY, f32::NEG_INFINITY);
+        let intervals = match roots[..] {
+            [t1, t2] if a > 0.0 => vec![(t1, t2)],
+            [t1, t2] => vec![(neg_inf, t1), (t2, inf)],
+            // the quadratic term vanishes along the ray
+            [t] if b > 0.0 => vec![(neg_inf, t)],
+            [t] => vec![(t, inf)],
+            _ if c < 0.0 => vec![(neg_inf, inf)],
+            _ => Vec::new(),
+        };
+        Intersec::from_intervals(intervals)
+    }
+
+    /// Normal is the gradient 2 M (x - c) + L
+    fn norm(&self, point: &Vector) -> Vector {
+        let p = point - &self.center;
+        (&(2.0 * &self.mul(&p)) + &self.l).norm()
+    }
+
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        let point = ray.point_on_ray(distance);
+        let norm = self.norm(&point);
+        Hit {
+            // clipped surface is open, so it's lit from both sides
+            norm: match self.bounds {
+                None => norm,
+                Some(_) => face_forward(norm, ray),
+            },
+            shading_norm: None,
+            color: None,
+            uv: None,
+            point,
+        }
+    }
+
+    /// Ellipsoid is bounded, other surfaces only by the clipping box
+    fn bounds(&self) -> Option<Aabb> {
+        let clip = self.bounds.map(|(min, max)| Aabb { min, max });
+        match (self.ellipsoid_bounds(), clip) {
+            (Some(e), Some(c)) => Some(e.intersection(&c)),
+            (e, c) => e.or(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::ray::Ray;
+    use crate::raytracer::scene::shape::quadric::new_paraboloid;
+    use crate::raytracer::scene::shape::Shape;
+    use crate::raytracer::vector::Vector;
+
+    #[test]
+    fn paraboloid_reflects_to_focus() {
+        let mirror = new_paraboloid(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), 0.5)
+            .clip(Vector::new(-2.0, -1.0, -2.0), Vector::new(2.0, 1.0, 2.0));
+        let focus = Vector::new(0.0, 0.5, 0.0);
+
+        // ray parallel to the axis
+        let ray = Ray::new(Vector::new(0.7, 5.0, 0.2), Vector::new(0.0, -1.0, 0.0));
+        let t = mirror.intersec(&ray).get_closer().unwrap();
+        let hit = mirror.hit(&ray, t);
+        let r = ray.get_dir().reflect(&hit.norm).unwrap();
+
+        let to_focus = (&focus - &hit.point).norm();
+        assert!((&r - &to_focus).size() < 1e-4, "{:?} != {:?}", r, to_focus);
+    }
+}
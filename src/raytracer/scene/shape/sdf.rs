@@ -0,0 +1,170 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Signed distance field: distance from the point to the surface, negative inside.
+///
+/// Fields of the fractals and of the smooth combinations are not exact distances, but they
+/// never overestimate it, so sphere tracing doesn't step over the surface.
+pub enum Field {
+    Sphere {
+        center: Vector,
+        radius: f32,
+    },
+    /// Box with edges rounded by the radius
+    RoundBox {
+        center: Vector,
+        half_extents: Vector,
+        radius: f32,
+    },
+    /// Torus lying in the XZ plane
+    Torus {
+        center: Vector,
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    /// Cylinder with hemispheres at the ends
+    Capsule {
+        start: Vector,
+        end: Vector,
+        radius: f32,
+    },
+    Mandelbulb {
+        center: Vector,
+        scale: f32,
+        power: f32,
+        iterations: usize,
+    },
+    Union(Box<Field>, Box<Field>),
+    Intersection(Box<Field>, Box<Field>),
+    /// left field without right one
+    Difference(Box<Field>, Box<Field>),
+    /// Union which blends the surfaces where they are closer than k
+    SmoothUnion(Box<Field>, Box<Field>, f32),
+}
+
+impl Field {
+    pub fn distance(&self, p: &Vector) -> f32 {
+        match self {
+            Field::Sphere { center, radius } => (p - center).size() - radius,
+            Field::RoundBox {
+                center,
+                half_extents,
+                radius,
+            } => {
+                let p = (p - center).to_arr();
+                let h = half_extents.to_arr();
+                let q = [0, 1, 2].map(|i| p[i].abs() - h[i] + radius);
+                let outside = Vector::new_from_arr(&q.map(|c| c.max(0.0))).size();
+                let inside = q[0].max(q[1]).max(q[2]).min(0.0);
+                outside + inside - radius
+            }
+            Field::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let [x, y, z] = (p - center).to_arr();
+                let q = (x * x + z * z).sqrt() - major_radius;
+                (q * q + y * y).sqrt() - minor_radius
+            }
+            Field::Capsule { start, end, radius } => {
+                let pa = p - start;
+                let ba = end - start;
+                let h = (pa.dot(&ba) / ba.dot2()).clamp(0.0, 1.0);
+                (&pa - &(h * &ba)).size() - radius
+            }
+            Field::Mandelbulb {
+                center,
+                scale,
+                power,
+                iterations,
+            } => scale * mandelbulb(&(1.0 / scale * &(p - center)), *power, *iterations),
+            Field::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Field::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Field::Difference(a, b) => a.distance(p).max(-b.distance(p)),
+            Field::SmoothUnion(a, b, k) => {
+                let (d1, d2) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+                d2 + (d1 - d2) * h - k * h * (1.0 - h)
+            }
+        }
+    }
+}
+
+/// Distance estimation of the Mandelbulb fractal of the power at the origin
+fn mandelbulb(p: &Vector, power: f32, iterations: usize) -> f32 {
+    const BAILOUT: f32 = 2.0;
+
+    let c = p.to_arr();
+    let mut z = c;
+    let mut dr = 1.0;
+    let mut r = 0.0;
+    for _ in 0..iterations {
+        r = Vector::new_from_arr(&z).size();
+        if r > BAILOUT {
+            break;
+        }
+        let theta = (z[2] / r).acos() * power;
+        let phi = z[1].atan2(z[0]) * power;
+        dr = r.powf(power - 1.0) * power * dr + 1.0;
+        let zr = r.powf(power);
+        z = [
+            zr * theta.sin() * phi.cos() + c[0],
+            zr * theta.sin() * phi.sin() + c[1],
+            zr * theta.cos() + c[2],
+        ];
+    }
+    if r == 0.0 {
+        return 0.0;
+    }
+    0.5 * r.ln() * r / dr
+}
+
+/// Shape given by the signed distance field, the ray is intersected by sphere tracing: it
+/// steps along the ray by the distance to the surface until the distance is less than
+/// epsilon.
+pub struct Sdf {
+    field: Field,
+    max_steps: usize,
+    epsilon: f32,
+    max_distance: f32,
+}
+
+pub fn new_sdf(field: Field, max_steps: usize, epsilon: f32, max_distance: f32) -> impl Shape {
+    Sdf {
+        field,
+        max_steps,
+        epsilon,
+        max_distance,
+    }
+}
+
+impl Shape for Sdf {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let mut t = 0.0;
+        for _ in 0..self.max_steps {
+            let d = self.field.distance(&ray.point_on_ray(t));
+            // rays going from the surface (shadow and reflection) must leave it first
+            if d < self.epsilon && t > 2.0 * self.epsilon {
+                return Intersec::One(t);
+            }
+            t += d.abs().max(self.epsilon);
+            if t > self.max_distance {
+                break;
+            }
+        }
+        Intersec::None
+    }
+
+    /// Normal is the gradient of the field found by central differences
+    fn norm(&self, point: &Vector) -> Vector {
+        let h = self.epsilon;
+        let [x, y, z] = point.to_arr();
+        let d = |dx: f32, dy: f32, dz: f32| {
+            self.field.distance(&Vector::new(x + dx, y + dy, z + dz))
+                - self.field.distance(&Vector::new(x - dx, y - dy, z - dz))
+        };
+        Vector::new(d(h, 0.0, 0.0), d(0.0, h, 0.0), d(0.0, 0.0, h)).norm()
+    }
+}
@@ -0,0 +1,97 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{poly, Aabb, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Torus given by its center, axis of the rotation symmetry, radius of the circle going
+/// through the center of the tube (major) and radius of the tube (minor).
+pub struct Torus {
+    center: Vector,
+    axis: Vector,
+    major: f32,
+    minor: f32,
+}
+
+pub fn new_torus(center: Vector, axis: Vector, major_radius: f32, minor_radius: f32) -> impl Shape {
+    Torus {
+        center,
+        axis: axis.norm(),
+        major: major_radius.abs(),
+        minor: minor_radius.abs(),
+    }
+}
+
+impl Shape for Torus {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        // quartic loses precision in f32
+        let to_f64 = |v: &Vector| v.to_arr().map(|c| c as f64);
+        let dot = |a: &[f64; 3], b: &[f64; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+        let p = to_f64(&(ray.get_orig() - &self.center));
+        let d = to_f64(ray.get_dir());
+        let w = to_f64(&self.axis);
+
+        // move the origin of the ray to the bounding sphere, far origin makes big
+        // coefficients of the quartic
+        let bound = (self.major + self.minor) as f64;
+        let (pp, pd, dd) = (dot(&p, &p), dot(&p, &d), dot(&d, &d));
+        let disc = pd * pd - dd * (pp - bound * bound);
+        if disc < 0.0 {
+            return Intersec::None;
+        }
+        let shift = (-pd - disc.sqrt()) / dd;
+        let p = [0, 1, 2].map(|i| p[i] + shift * d[i]);
+
+        let (pp, pd) = (dot(&p, &p), dot(&p, &d));
+        let (pw, dw) = (dot(&p, &w), dot(&d, &w));
+        let r2 = (self.major as f64).powi(2);
+        let k = 4.0 * r2;
+
+        // (|x|^2 + R^2 - r^2)^2 = 4 R^2 (|x|^2 - (x w)^2), where x = p + t d
+        let alpha = dd;
+        let beta = 2.0 * pd;
+        let gamma = pp + r2 - (self.minor as f64).powi(2);
+        let coeffs = [
+            alpha * alpha,
+            2.0 * alpha * beta,
+            beta * beta + 2.0 * alpha * gamma - k * (dd - dw * dw),
+            2.0 * beta * gamma - 2.0 * k * (pd - pw * dw),
+            gamma * gamma - k * (pp - pw * pw),
+        ];
+
+        let mut roots = poly::solve(&coeffs);
+        if roots.len() % 2 == 1 {
+            // the ray touches the torus, the root of the touch is the double one
+            let deriv = poly::derivative(&coeffs);
+            let touch = roots.iter().copied().min_by(|a, b| {
+                poly::eval(&deriv, *a)
+                    .abs()
+                    .total_cmp(&poly::eval(&deriv, *b).abs())
+            });
+            if let Some(touch) = touch {
+                roots.push(touch);
+                roots.sort_by(f64::total_cmp);
+            }
+        }
+        let roots: Vec<f32> = roots.iter().map(|t| (t + shift) as f32).collect();
+
+        match roots[..] {
+            [t1, t2] => Intersec::Two(t1, t2),
+            [t1, t2, t3, t4] => Intersec::Four(t1, t2, t3, t4),
+            _ => Intersec::None,
+        }
+    }
+
+    fn norm(&self, point: &Vector) -> Vector {
+        let p = point - &self.center;
+        let radial = &p - &(p.dot(&self.axis) * &self.axis);
+        let tube_center = self.major * &radial.norm();
+        (&p - &tube_center).norm()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let b = Aabb::around_disk(&self.center, &self.axis, self.major);
+        Some(Aabb {
+            min: b.min.map(|c| c - self.minor),
+            max: b.max.map(|c| c + self.minor),
+        })
+    }
+}
@@ -0,0 +1,72 @@
+use crate::raytracer::matrix::Matrix;
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{Aabb, Hit, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Shape moved to the world by the affine transformation.
+///
+/// Rays are moved to the space of the shape by the inverse transformation, so any shape can
+/// be translated, rotated or scaled (non uniformly too) without knowing about it.
+pub struct Transformed {
+    shape: Box<dyn Shape>,
+    /// from the world to the space of the shape
+    to_object: Matrix,
+    /// normals are transformed by the inverse transpose matrix to stay perpendicular to the
+    /// surface after non uniform scale
+    to_world_norm: Matrix,
+}
+
+/// Return None if the transformation is singular (scale by zero)
+pub fn new_transformed(shape: Box<dyn Shape>, to_world: &Matrix) -> Option<Transformed> {
+    let to_object = to_world.inverse()?;
+    Some(Transformed {
+        shape,
+        to_world_norm: to_object.transpose(),
+        to_object,
+    })
+}
+
+impl Transformed {
+    /// Return the ray in the space of the shape with normalized direction and the factor
+    /// which turns distances along the world ray into distances along the object one
+    fn object_ray(&self, ray: &Ray) -> (Ray, f32) {
+        let dir = self.to_object.vector(ray.get_dir());
+        let k = dir.size();
+        (
+            Ray::new(self.to_object.point(ray.get_orig()), dir.norm()),
+            k,
+        )
+    }
+
+    fn world_norm(&self, n: &Vector) -> Vector {
+        self.to_world_norm.vector(n).norm()
+    }
+}
+
+impl Shape for Transformed {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        let (obj_ray, k) = self.object_ray(ray);
+        self.shape.intersec(&obj_ray).map(|d| d / k)
+    }
+
+    fn norm(&self, point: &Vector) -> Vector {
+        self.world_norm(&self.shape.norm(&self.to_object.point(point)))
+    }
+
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        let (obj_ray, k) = self.object_ray(ray);
+        let hit = self.shape.hit(&obj_ray, distance * k);
+        Hit {
+            point: ray.point_on_ray(distance),
+            norm: self.world_norm(&hit.norm),
+            shading_norm: hit.shading_norm.as_ref().map(|n| self.world_norm(n)),
+            ..hit
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let to_world = self.to_object.inverse()?;
+        let corners = self.shape.bounds()?.corners().map(|p| to_world.point(&p));
+        Some(Aabb::from_points(corners.iter()))
+    }
+}
@@ -0,0 +1,122 @@
+use crate::raytracer::ray::Ray;
+use crate::raytracer::scene::shape::{face_forward, Aabb, Hit, Intersec, Shape};
+use crate::raytracer::vector::Vector;
+
+/// Triangle given by its vertices, the normal looks to the side from which the vertices
+/// go counterclockwise.
+pub struct Triangle {
+    a: Vector,
+    b: Vector,
+    c: Vector,
+    normal: Vector,
+}
+
+pub fn new_triangle(a: Vector, b: Vector, c: Vector) -> impl Shape {
+    Triangle {
+        normal: face_norm(&a, &b, &c),
+        a,
+        b,
+        c,
+    }
+}
+
+impl Shape for Triangle {
+    fn intersec(&self, ray: &Ray) -> Intersec {
+        match intersec_triangle(ray, &self.a, &self.b, &self.c) {
+            None => Intersec::None,
+            Some((t, _, _)) => Intersec::One(t),
+        }
+    }
+
+    fn norm(&self, _point: &Vector) -> Vector {
+        self.normal.clone()
+    }
+
+    /// Texture coordinates are the barycentric coordinates of the vertices b and c
+    fn hit(&self, ray: &Ray, distance: f32) -> Hit {
+        let uv = intersec_triangle(ray, &self.a, &self.b, &self.c).map(|(_, u, v)| (u, v));
+        Hit {
+            point: ray.point_on_ray(distance),
+            norm: face_forward(self.normal.clone(), ray),
+            shading_norm: None,
+            color: None,
+            uv,
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_points([&self.a, &self.b, &self.c]))
+    }
+}
+
+/// Return normal of the triangle face
+pub(super) fn face_norm(a: &Vector, b: &Vector, c: &Vector) -> Vector {
+    (b - a).cross(&(c - a)).norm()
+}
+
+/// Intersection of the ray with the triangle by Moller-Trumbore algorithm.
+///
+/// Return distance to the hit point and its barycentric coordinates (u, v) so the point is
+/// a + u * (b - a) + v * (c - a)
+pub(super) fn intersec_triangle(
+    ray: &Ray,
+    a: &Vector,
+    b: &Vector,
+    c: &Vector,
+) -> Option<(f32, f32, f32)> {
+    let ab = b - a;
+    let ac = c - a;
+    let p = ray.get_dir().cross(&ac);
+    let det = ab.dot(&p);
+    // det scales with the area of the triangle, only the ray parallel to the plane misses
+    // it however small the triangle is
+    let inv_det = 1.0 / det;
+    if det == 0.0 || !inv_det.is_finite() {
+        return None;
+    }
+
+    let ao = ray.get_orig() - a;
+    let u = ao.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = ao.cross(&ab);
+    let v = ray.get_dir().dot(&q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = ac.dot(&q) * inv_det;
+    if t <= 0.0 {
+        return None;
+    }
+    Some((t, u, v))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raytracer::ray::Ray;
+    use crate::raytracer::scene::shape::triangle::intersec_triangle;
+    use crate::raytracer::vector::Vector;
+
+    #[test]
+    fn tiny_triangle_is_hit() {
+        let size = 1e-5;
+        let (a, b, c) = (
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(size, 0.0, 1.0),
+            Vector::new(0.0, size, 1.0),
+        );
+        let ray = Ray::new(
+            Vector::new(size / 4.0, size / 4.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+        );
+        let (t, u, v) = intersec_triangle(&ray, &a, &b, &c).unwrap();
+        assert!((t - 1.0).abs() < 1e-6);
+        assert!((u - 0.25).abs() < 1e-3 && (v - 0.25).abs() < 1e-3);
+
+        let parallel = Ray::new(Vector::new(0.0, 0.0, 1.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(intersec_triangle(&parallel, &a, &b, &c).is_none());
+    }
+}
@@ -1,4 +1,4 @@
-use rand;
+use rand::Rng;
 use std::ops::{Add, AddAssign, Mul, Sub};
 
 /// Vector in 3 dimension Euclidean space.
@@ -15,28 +15,29 @@ impl Vector {
         Vector { x, y, z }
     }
 
-    /// Return new Vector with random direction and random size in [0, 1].
+    /// Return new Vector with random direction and random size in [0, 1] drawn from the
+    /// given generator, so every render thread uses its own one.
     ///
     /// # Example
     /// ```rust
     /// // create Vector r with r.size() <= 1.0 and random direction
-    /// let r = Vector::new_rand();
+    /// let r = Vector::new_rand(&mut rng);
     /// ```
     ///
     /// # Use case
     /// In this project random Vectors used for diffusion by change direction of ideal shape's
     /// normal Vectors:
     /// ```rust
-    /// let normal_with_diffusion = (normal_ideal + Vector::new_rand() * diffusion_coefficient).norm();
+    /// let normal_with_diffusion = (normal_ideal + Vector::new_rand(&mut rng) * diffusion_coefficient).norm();
     /// ```
-    pub fn new_rand() -> Vector {
+    pub fn new_rand<R: Rng>(rng: &mut R) -> Vector {
         (Vector {
-            x: rand::random(),
-            y: rand::random(),
-            z: rand::random(),
+            x: rng.gen(),
+            y: rng.gen(),
+            z: rng.gen(),
         }
         .norm())
-            * rand::random()
+            * rng.gen::<f32>()
     }
 
     /// Return new Vector with given coordinates from array.
@@ -57,6 +58,13 @@ impl Vector {
         }
     }
 
+    /// Return coordinates of the Vector as array (X, Y, Z).
+    ///
+    /// It's handy when the same computation should be done for every axis.
+    pub fn to_arr(&self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+
     /// Return scalar or dot product of two Vectors (scalar)
     ///
     /// #Example